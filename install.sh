@@ -16,6 +16,8 @@ rm -rf ~/.config/global_pomodoro
 mkdir -p ~/.config/global_pomodoro
 cp blocked_sites.json ~/.config/global_pomodoro/
 cp pomodoro_config.json ~/.config/global_pomodoro/
+cp allowed_origins.json ~/.config/global_pomodoro/
+cp profiles.json ~/.config/global_pomodoro/
 
 echo "✅ Instalación completa."
 echo "ℹ️  La configuración ha sido restablecida a los valores por defecto."
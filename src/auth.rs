@@ -0,0 +1,107 @@
+use std::{collections::HashSet, fs, io::{Read, Write}, os::unix::fs::OpenOptionsExt, path::Path};
+
+use tokio_tungstenite::tungstenite::http::Request;
+
+use crate::config::data_file;
+
+/// Id de nuestra extensión de Chrome, fijado al compilar con
+/// `POMODORO_EXTENSION_ID`. Su origen se acepta aunque no esté en
+/// `allowed_origins.json`; cualquier otra extensión tiene que estar listada.
+const EXTENSION_ID: Option<&str> = option_env!("POMODORO_EXTENSION_ID");
+
+pub fn token_path() -> String {
+    data_file("auth_token")
+}
+
+pub fn allowed_origins_path() -> String {
+    data_file("allowed_origins.json")
+}
+
+/// Lee el token de la instalación o genera uno nuevo con permisos 0600.
+pub fn load_or_create_token() -> std::io::Result<String> {
+    let path = token_path();
+    if Path::new(&path).exists() {
+        let token = fs::read_to_string(&path)?.trim().to_string();
+        if !token.is_empty() {
+            return Ok(token);
+        }
+    }
+
+    let mut bytes = [0u8; 32];
+    fs::File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    let token: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&path)?;
+    file.write_all(token.as_bytes())?;
+    println!("🔑 Token de autenticación generado en {}", path);
+    Ok(token)
+}
+
+pub fn read_allowed_origins() -> HashSet<String> {
+    let content = fs::read_to_string(allowed_origins_path()).unwrap_or_default();
+    serde_json::from_str(&content).unwrap_or_default()
+}
+
+/// Los clientes nativos no envían `Origin`; los navegadores sí, y solo se
+/// aceptan los orígenes exactos de la lista y el de nuestra extensión.
+pub fn is_origin_allowed(origin: Option<&str>, allowlist: &HashSet<String>) -> bool {
+    match origin {
+        None => true,
+        Some(origin) => {
+            allowlist.contains(origin) || default_extension_origin().is_some_and(|own| own == origin)
+        }
+    }
+}
+
+fn default_extension_origin() -> Option<String> {
+    EXTENSION_ID.map(|id| format!("chrome-extension://{}", id))
+}
+
+/// Comparación en tiempo constante para no filtrar el token por temporización.
+pub fn tokens_match(expected: &str, given: &str) -> bool {
+    if expected.len() != given.len() {
+        return false;
+    }
+    expected
+        .bytes()
+        .zip(given.bytes())
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+        == 0
+}
+
+/// Token enviado en la petición, vía `Authorization: Bearer` o `?token=`.
+pub fn token_from_request<B>(req: &Request<B>) -> Option<String> {
+    if let Some(value) = req.headers().get("authorization").and_then(|v| v.to_str().ok()) {
+        if let Some(token) = value.strip_prefix("Bearer ") {
+            return Some(token.trim().to_string());
+        }
+    }
+
+    req.uri().query().and_then(|query| {
+        query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("token=").map(|t| t.to_string()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_listed_extensions_are_allowed() {
+        let allowlist: HashSet<String> = ["moz-extension://1b2c3d4e-aaaa-bbbb-cccc-000000000000".to_string()].into();
+        assert!(is_origin_allowed(None, &allowlist));
+        assert!(is_origin_allowed(Some("moz-extension://1b2c3d4e-aaaa-bbbb-cccc-000000000000"), &allowlist));
+        assert!(!is_origin_allowed(Some("moz-extension://otra"), &allowlist));
+        if EXTENSION_ID.is_none() {
+            assert!(!is_origin_allowed(Some("chrome-extension://abcdefghijklmnop"), &allowlist));
+        }
+        assert!(!is_origin_allowed(Some("https://example.com"), &allowlist));
+    }
+}
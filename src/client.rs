@@ -1,15 +1,251 @@
 use std::io::{BufRead, BufReader, Write};
-use std::net::TcpStream;
+use std::os::unix::net::UnixStream;
 
-pub fn send_command(command: &str) {
-    if let Ok(mut stream) = TcpStream::connect("127.0.0.1:7878") {
-        stream.write_all(format!("{}\n", command).as_bytes()).unwrap();
+use serde_json::{Map, Value};
 
-        let mut reader = BufReader::new(stream);
-        let mut response = String::new();
-        reader.read_line(&mut response).unwrap();
-        println!("{response}");
+use crate::export::{self, ExportFormat};
+use crate::messages::{render, Locale};
+use crate::protocol::ResponsePayload;
+use crate::unix_socket::socket_path;
+
+type Request = Map<String, Value>;
+
+/// Qué hacer con la respuesta en lugar de mostrarla como texto.
+#[derive(Default)]
+struct Output {
+    /// `report --json`: las estadísticas en JSON
+    json: bool,
+    /// `history export --output`: el archivo donde guardar la exportación
+    file: Option<String>,
+}
+
+/// Envía `command` con sus argumentos al servidor por el socket Unix y
+/// muestra la respuesta. `--timer <id>` lo dirige a un temporizador secundario.
+pub fn send_command(command: &str, args: &[String]) {
+    let mut request = Request::new();
+    request.insert("command".to_string(), command.into());
+    let mut args = args.to_vec();
+
+    let parsed = take_timer_arg(&mut args, &mut request).and_then(|()| match command {
+        "extend" | "shorten" => parse_seconds_args(command, &args, &mut request),
+        "useprofile" | "deleteprofile" => parse_profile_args(command, &args, &mut request),
+        "addtask" => parse_addtask_args(&args, &mut request),
+        "settask" | "deletetask" | "donetask" => parse_task_id_args(command, &args, &mut request),
+        "history" => parse_history_args(&args, &mut request),
+        "report" => parse_stats_args(&args, &mut request),
+        "createtimer" => parse_createtimer_args(&args, &mut request),
+        "removetimer" => parse_removetimer_args(&args, &mut request),
+        _ => Ok(Output::default()),
+    });
+    match parsed {
+        Ok(output) => send(&request, output),
+        Err(message) => eprintln!("❌ {}", message),
+    }
+}
+
+fn take_timer_arg(args: &mut Vec<String>, request: &mut Request) -> Result<(), String> {
+    let Some(index) = args.iter().position(|arg| arg == "--timer") else {
+        return Ok(());
+    };
+    if index + 1 >= args.len() {
+        return Err("Uso: --timer <id>".to_string());
+    }
+    let timer = args.remove(index + 1);
+    args.remove(index);
+    request.insert("timer".to_string(), timer.into());
+    Ok(())
+}
+
+fn parse_seconds_args(command: &str, args: &[String], request: &mut Request) -> Result<Output, String> {
+    let seconds = args
+        .first()
+        .and_then(|arg| arg.parse::<u32>().ok())
+        .ok_or_else(|| format!("Uso: {} <segundos>", command))?;
+    request.insert("seconds".to_string(), seconds.into());
+    Ok(Output::default())
+}
+
+/// `useprofile` acepta `--now` para aplicar el perfil sin esperar al final de la fase.
+fn parse_profile_args(command: &str, args: &[String], request: &mut Request) -> Result<Output, String> {
+    let name = args
+        .iter()
+        .find(|arg| !arg.starts_with("--"))
+        .ok_or_else(|| format!("Uso: {} <perfil>", command))?;
+    request.insert("name".to_string(), name.as_str().into());
+    if args.iter().any(|arg| arg == "--now") {
+        request.insert("immediately".to_string(), true.into());
+    }
+    Ok(Output::default())
+}
+
+fn parse_addtask_args(args: &[String], request: &mut Request) -> Result<Output, String> {
+    let usage = || "Uso: addtask <título> [--estimate <pomodoros>] [--tag <etiqueta>]".to_string();
+    let mut title = Vec::new();
+    let mut tags = Vec::new();
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--tag" => tags.push(Value::from(rest.next().ok_or_else(usage)?.as_str())),
+            "--estimate" => {
+                let estimate = rest.next().and_then(|value| value.parse::<u32>().ok()).ok_or_else(usage)?;
+                request.insert("estimate".to_string(), estimate.into());
+            }
+            _ => title.push(arg.as_str()),
+        }
+    }
+    request.insert("title".to_string(), title.join(" ").into());
+    request.insert("tags".to_string(), tags.into());
+    Ok(Output::default())
+}
+
+/// `settask none` deja sin tarea activa; `donetask` es un `updatetask` con `done`.
+fn parse_task_id_args(command: &str, args: &[String], request: &mut Request) -> Result<Output, String> {
+    match args.first().map(String::as_str) {
+        Some("none") if command == "settask" => {}
+        Some(id) => {
+            let id = id.parse::<u32>().map_err(|_| format!("Uso: {} <id>", command))?;
+            request.insert("id".to_string(), id.into());
+        }
+        None => return Err(format!("Uso: {} <id>", command)),
+    }
+    if command == "donetask" {
+        request.insert("command".to_string(), "updatetask".into());
+        request.insert("done".to_string(), true.into());
+    }
+    Ok(Output::default())
+}
+
+/// `history`, `history export ...` o `history import <archivo>`.
+fn parse_history_args(args: &[String], request: &mut Request) -> Result<Output, String> {
+    match args.first().map(String::as_str) {
+        Some("import") => parse_import_args(&args[1..], request),
+        Some("export") => parse_export_args(&args[1..], request),
+        _ => parse_filter_args("history", args, request),
+    }
+}
+
+/// Lee un CSV o JSON exportado; el formato sale de la extensión.
+fn parse_import_args(args: &[String], request: &mut Request) -> Result<Output, String> {
+    let path = args.first().ok_or("Uso: history import <archivo.csv|archivo.json>")?;
+    let format = if path.ends_with(".csv") {
+        ExportFormat::Csv
+    } else if path.ends_with(".ics") {
+        ExportFormat::Ics
     } else {
+        ExportFormat::Json
+    };
+    let content = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let sessions = export::parse(&content, format).map_err(|e| format!("{}: {}", path, e.message))?;
+    let sessions = serde_json::to_value(sessions).map_err(|e| format!("{}: {}", path, e))?;
+    request.insert("command".to_string(), "importhistory".into());
+    request.insert("sessions".to_string(), sessions);
+    Ok(Output::default())
+}
+
+/// `--format csv|json|ics` (CSV por defecto) y `--output <archivo>`, además
+/// de los filtros de `history`.
+fn parse_export_args(args: &[String], request: &mut Request) -> Result<Output, String> {
+    let mut args = args.to_vec();
+    let mut format = "csv".to_string();
+    let mut output = Output::default();
+    while let Some(index) = args.iter().position(|arg| arg == "--format" || arg == "--output") {
+        if index + 1 >= args.len() {
+            return Err("Uso: history export [--format csv|json|ics] [--output <archivo>]".to_string());
+        }
+        let value = args.remove(index + 1);
+        if args.remove(index) == "--format" {
+            format = value;
+        } else {
+            output.file = Some(value);
+        }
+    }
+    request.insert("command".to_string(), "exporthistory".into());
+    request.insert("format".to_string(), format.into());
+    parse_filter_args("history export", &args, request)?;
+    Ok(output)
+}
+
+/// `report` pide `stats`; con `--json` se imprime el JSON tal cual.
+fn parse_stats_args(args: &[String], request: &mut Request) -> Result<Output, String> {
+    let (json, filters): (Vec<String>, Vec<String>) = args.iter().cloned().partition(|arg| arg == "--json");
+    request.insert("command".to_string(), "stats".into());
+    parse_filter_args("report", &filters, request)?;
+    Ok(Output { json: !json.is_empty(), ..Output::default() })
+}
+
+/// `--from` y `--to` (`AAAA-MM-DD`) acotan los días; `--phase`, `--task`,
+/// `--tag` y `--profile` van en `filter`.
+fn parse_filter_args(command: &str, args: &[String], request: &mut Request) -> Result<Output, String> {
+    let mut filter = Map::new();
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        let (Some(name), Some(value)) = (arg.strip_prefix("--"), rest.next()) else {
+            return Err(format!(
+                "Uso: {} [--from <día>] [--to <día>] [--phase <fase>] [--task <id>] [--tag <etiqueta>] [--profile <perfil>]",
+                command
+            ));
+        };
+        match name {
+            "from" | "to" => {
+                request.insert(name.to_string(), value.as_str().into());
+            }
+            "task" => {
+                let id = value.parse::<u32>().map_err(|_| "Uso: --task <id>".to_string())?;
+                filter.insert(name.to_string(), id.into());
+            }
+            _ => {
+                filter.insert(name.to_string(), value.as_str().into());
+            }
+        }
+    }
+    if !filter.is_empty() {
+        request.insert("filter".to_string(), filter.into());
+    }
+    Ok(Output::default())
+}
+
+fn parse_createtimer_args(args: &[String], request: &mut Request) -> Result<Output, String> {
+    let (Some(id), Some(duration)) = (args.first(), args.get(1).and_then(|arg| arg.parse::<u32>().ok())) else {
+        return Err("Uso: createtimer <id> <segundos> [etiqueta]".to_string());
+    };
+    request.insert("id".to_string(), id.as_str().into());
+    request.insert("duration".to_string(), duration.into());
+    if args.len() > 2 {
+        request.insert("label".to_string(), args[2..].join(" ").into());
+    }
+    Ok(Output::default())
+}
+
+fn parse_removetimer_args(args: &[String], request: &mut Request) -> Result<Output, String> {
+    let id = args.first().ok_or("Uso: removetimer <id>")?;
+    request.insert("id".to_string(), id.as_str().into());
+    Ok(Output::default())
+}
+
+fn send(request: &Request, output: Output) {
+    let Ok(mut stream) = UnixStream::connect(socket_path()) else {
         eprintln!("❌ No se pudo conectar al servidor Pomodoro");
+        return;
+    };
+    let request = serde_json::to_string(request).unwrap();
+    stream.write_all(format!("{}\n", request).as_bytes()).unwrap();
+
+    let mut reader = BufReader::new(stream);
+    let mut response = String::new();
+    reader.read_line(&mut response).unwrap();
+    match serde_json::from_str::<ResponsePayload>(&response) {
+        Ok(ResponsePayload::Stats(stats)) if output.json => {
+            println!("{}", serde_json::to_string_pretty(&stats).unwrap_or(response));
+        }
+        // Tal cual, sin salto de línea extra: el iCalendar termina en CRLF
+        Ok(ResponsePayload::HistoryExported { content, .. }) => match output.file {
+            Some(path) => match std::fs::write(&path, content) {
+                Ok(()) => println!("💾 Historial exportado a {}", path),
+                Err(e) => eprintln!("❌ No se pudo escribir {}: {}", path, e),
+            },
+            None => print!("{}", content),
+        },
+        Ok(payload) => println!("{}", render(&payload, Locale::from_env())),
+        Err(_) => println!("{response}"),
     }
 }
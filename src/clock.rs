@@ -0,0 +1,62 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Duration, Local};
+
+pub type Sleep<'a> = Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+
+/// Tiempo que usan el motor del temporizador y la agenda: la hora de pared y
+/// las esperas entre ticks. Se puede sustituir por `FakeClock` para probarlos.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Local>;
+    fn sleep(&self, duration: std::time::Duration) -> Sleep<'_>;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Local> {
+        Local::now()
+    }
+
+    fn sleep(&self, duration: std::time::Duration) -> Sleep<'_> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+/// Reloj que solo avanza cuando se le pide o cuando alguien duerme. Las
+/// esperas usan el tiempo de tokio, así que con el tiempo en pausa
+/// (`start_paused`) una fase de 25 minutos pasa en milisegundos y la hora
+/// de pared avanza a la par.
+pub struct FakeClock {
+    now: Mutex<DateTime<Local>>,
+}
+
+impl FakeClock {
+    pub fn new(now: DateTime<Local>) -> Self {
+        Self { now: Mutex::new(now) }
+    }
+
+    pub fn set(&self, now: DateTime<Local>) {
+        *self.now.lock().unwrap() = now;
+    }
+
+    pub fn advance(&self, by: Duration) {
+        let mut now = self.now.lock().unwrap();
+        *now += by;
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> DateTime<Local> {
+        *self.now.lock().unwrap()
+    }
+
+    fn sleep(&self, duration: std::time::Duration) -> Sleep<'_> {
+        Box::pin(async move {
+            tokio::time::sleep(duration).await;
+            self.advance(Duration::from_std(duration).unwrap_or_default());
+        })
+    }
+}
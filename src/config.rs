@@ -1,8 +1,11 @@
 use std::{env, path::Path};
 
 use serde::{Deserialize, Serialize};
+
+use crate::history::SessionEvent;
+use crate::profiles::PendingProfile;
 use serde_json::{self, Error};
-use tokio::fs::{self, File, OpenOptions};
+use tokio::fs::OpenOptions;
 use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader};
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
@@ -13,35 +16,515 @@ pub enum Phase {
     Idle,
 }
 
+/// Estado del motor del temporizador. `Finished` significa que el ciclo
+/// completo terminó (la fase es `Idle`); `Idle` que aún no se ha iniciado.
+/// `WaitingForConfirmation`: la fase actual no arranca sola y espera a
+/// `continue`.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimerState {
+    #[default]
+    Idle,
+    Running,
+    Paused,
+    WaitingForConfirmation,
+    Finished,
+}
+
+/// Un paso de una secuencia de fases. `kind` dice si cuenta como trabajo o
+/// como descanso; el resto describe cómo se anuncia y si bloquea webs.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct PhaseStep {
+    pub name: String,
+    pub kind: Phase,
+    pub duration: u32,
+    /// Bloquea las webs de la lista mientras dura el paso
+    #[serde(default)]
+    pub block_sites: bool,
+    /// Archivo de la carpeta de sonidos; si falta, el de su `kind`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sound: Option<String>,
+    /// Texto de la notificación; si falta, el de su `kind`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notification: Option<String>,
+    /// Si falta, se usa `auto_start` según su `kind`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_start: Option<bool>,
+}
+
+/// `Pomodoro` cuenta hacia atrás duraciones fijas; `Flow` deja que el
+/// trabajo cuente hacia arriba hasta que se termina con `skip`, y calcula la
+/// pausa a partir del tiempo trabajado.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimerMode {
+    #[default]
+    Pomodoro,
+    Flow,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct FlowSettings {
+    /// Duración de la pausa como porcentaje del tiempo trabajado (20 = un quinto)
+    pub break_percent: u32,
+    /// Bloquea las webs de la lista durante todo el periodo de flujo
+    pub block_sites: bool,
+}
+
+impl Default for FlowSettings {
+    fn default() -> Self {
+        Self {
+            break_percent: 20,
+            block_sites: true,
+        }
+    }
+}
+
+/// Qué fases empiezan solas al terminar la anterior. Las que no, esperan a
+/// `continue`; con `nag_minutes`, se recuerda cada tantos minutos de espera.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct AutoStartSettings {
+    pub work: bool,
+    pub short_break: bool,
+    pub long_break: bool,
+    pub nag_minutes: Option<u32>,
+}
+
+impl Default for AutoStartSettings {
+    fn default() -> Self {
+        Self {
+            work: true,
+            short_break: true,
+            long_break: true,
+            nag_minutes: None,
+        }
+    }
+}
+
+impl AutoStartSettings {
+    pub fn for_phase(&self, phase: Phase) -> bool {
+        match phase {
+            Phase::Work => self.work,
+            Phase::ShortBreak => self.short_break,
+            Phase::LongBreak => self.long_break,
+            Phase::Idle => true,
+        }
+    }
+}
+
+/// Fuente del tiempo de inactividad (ver `idle`).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(tag = "kind")]
+pub enum IdleSourceSettings {
+    #[default]
+    Disabled,
+    /// Comando que imprime los milisegundos de inactividad (`xprintidle`)
+    Command { command: String },
+    Logind,
+}
+
+/// Qué hacer cuando el usuario se ausenta durante una fase de trabajo.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IdlePolicy {
+    /// Pausar el temporizador
+    #[default]
+    Pause,
+    /// Seguir contando, pero anotar la ausencia
+    MarkAway,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct IdleSettings {
+    pub source: IdleSourceSettings,
+    /// Segundos sin actividad a partir de los que se considera ausencia
+    pub threshold_secs: u32,
+    pub policy: IdlePolicy,
+}
+
+impl Default for IdleSettings {
+    fn default() -> Self {
+        Self {
+            source: IdleSourceSettings::Disabled,
+            threshold_secs: 5 * 60,
+            policy: IdlePolicy::Pause,
+        }
+    }
+}
+
+/// Cómo se detectan las suspensiones (ver `suspend`). Se lee al arrancar
+/// el servidor.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SuspendSource {
+    /// Saltos entre el reloj de pared y el monotónico
+    #[default]
+    WallClock,
+    /// Señales de logind: avisa antes de suspender y también de bloqueos
+    Logind,
+    Disabled,
+}
+
+/// Qué hacer con el tiempo que el equipo pasa suspendido.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SuspendPolicy {
+    /// El temporizador no avanza mientras el equipo duerme
+    #[default]
+    Pause,
+    /// El tiempo suspendido se descuenta de la fase al despertar
+    Count,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct SuspendSettings {
+    pub source: SuspendSource,
+    pub policy: SuspendPolicy,
+    /// Pausar mientras la pantalla está bloqueada (solo con logind)
+    pub pause_on_lock: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Weekday {
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+    Sun,
+}
+
+impl From<chrono::Weekday> for Weekday {
+    fn from(day: chrono::Weekday) -> Self {
+        match day {
+            chrono::Weekday::Mon => Weekday::Mon,
+            chrono::Weekday::Tue => Weekday::Tue,
+            chrono::Weekday::Wed => Weekday::Wed,
+            chrono::Weekday::Thu => Weekday::Thu,
+            chrono::Weekday::Fri => Weekday::Fri,
+            chrono::Weekday::Sat => Weekday::Sat,
+            chrono::Weekday::Sun => Weekday::Sun,
+        }
+    }
+}
+
+/// Sesión que arranca sola ciertos días a una hora (`"09:00"`).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ScheduledSession {
+    pub days: Vec<Weekday>,
+    pub start: String,
+    /// Sustituye a `cycles` para esta sesión
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cycles: Option<u32>,
+    /// Fin de la ventana de bloqueo; si falta, `end_of_day`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<String>,
+}
+
+/// Agenda semanal (ver `schedule`).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct ScheduleSettings {
+    pub sessions: Vec<ScheduledSession>,
+    /// A esta hora se para el temporizador y se levantan los bloqueos
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_of_day: Option<String>,
+    /// Bloquear las webs durante las ventanas de la agenda, aunque el
+    /// temporizador no esté en marcha
+    pub block_sites: bool,
+}
+
+impl Default for ScheduleSettings {
+    fn default() -> Self {
+        Self {
+            sessions: Vec::new(),
+            end_of_day: None,
+            block_sites: true,
+        }
+    }
+}
+
+/// Lee una hora `HH:MM` de la agenda.
+pub fn parse_clock_time(text: &str) -> Option<chrono::NaiveTime> {
+    chrono::NaiveTime::parse_from_str(text, "%H:%M").ok()
+}
+
+/// Por qué pausó el propio servidor el temporizador, para reanudarlo solo
+/// si la pausa fue suya.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseCause {
+    Suspend,
+    Lock,
+    /// Ausencia con la política `Pause`
+    Idle,
+}
+
+/// Ausencia detectada en una fase de trabajo. Cuando el usuario vuelve
+/// (`returned`), decide con `resolveaway` si ese tiempo cuenta o no.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AwayPeriod {
+    /// Marca de tiempo Unix de la última actividad antes de irse
+    pub started_at: i64,
+    /// Segundos de ausencia que el temporizador ha contado
+    pub seconds: u32,
+    pub phase: Phase,
+    pub cycle: u32,
+    pub returned: bool,
+}
+
+/// Sonidos del patrón clásico y del final del ciclo, como nombres de archivo
+/// de la carpeta de sonidos. Los que faltan usan el sonido de siempre.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct SoundSettings {
+    pub work: Option<String>,
+    pub short_break: Option<String>,
+    pub long_break: Option<String>,
+    pub finished: Option<String>,
+}
+
+impl SoundSettings {
+    pub fn for_phase(&self, phase: Phase) -> Option<&String> {
+        match phase {
+            Phase::Work => self.work.as_ref(),
+            Phase::ShortBreak => self.short_break.as_ref(),
+            Phase::LongBreak => self.long_break.as_ref(),
+            Phase::Idle => self.finished.as_ref(),
+        }
+    }
+}
+
+/// Ajustes del servidor WebSocket; no afectan al temporizador.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ServerSettings {
+    /// Cada cuánto se envía un ping a cada cliente
+    pub ping_interval_secs: u64,
+    /// Sin pong (ni ningún otro mensaje) en este tiempo, el cliente se da por muerto.
+    /// También es el tiempo máximo que un cliente puede tener la cola llena.
+    pub pong_timeout_secs: u64,
+    /// Mensajes pendientes por cliente antes de empezar a descartar estados
+    pub client_queue_size: usize,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        Self {
+            ping_interval_secs: 15,
+            pong_timeout_secs: 45,
+            client_queue_size: 32,
+        }
+    }
+}
+
+/// Modelo de ciclos (ver `next_phase`): un ciclo es un bloque de trabajo
+/// seguido de su pausa. `cycles` cuenta bloques de trabajo y cada
+/// `long_break_interval` bloques la pausa es larga. Tras el último bloque
+/// llega una pausa larga si `final_long_break` y luego el temporizador
+/// termina, salvo con `repeat`, que vuelve a empezar sin parar.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct PomodoroStateConfig {
     pub work_duration: u32,
     pub break_duration: u32,
     pub long_break_duration: u32,
     pub cycles: u32,
+    /// Bloques de trabajo terminados
     pub current_cycle: u32,
-    pub is_running: bool,
+    /// Solo se lee de archivos antiguos sin `state`; el estado es `state`
+    #[serde(default, rename = "is_running", skip_serializing)]
+    legacy_running: bool,
     pub long_break_interval: u32,
+    #[serde(default = "default_true")]
+    pub final_long_break: bool,
+    #[serde(default)]
+    pub repeat: bool,
+    /// Pomodoros al día que cuentan para las rachas de `stats`
+    #[serde(default = "default_daily_goal")]
+    pub daily_goal: u32,
     pub time_left: u32,
     pub current_phase: Phase,
+    #[serde(default)]
+    pub state: TimerState,
+    #[serde(default)]
+    pub mode: TimerMode,
+    #[serde(default)]
+    pub flow: FlowSettings,
+    /// Segundos trabajados en la fase de flujo actual
+    #[serde(default)]
+    pub flow_elapsed: u32,
+    /// Pausa calculada al terminar la última fase de flujo
+    #[serde(default)]
+    pub flow_break: u32,
+    /// Secuencia personalizada que se repite `cycles` veces. Vacía, se usa
+    /// el patrón clásico de trabajo y pausas corta y larga.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sequence: Vec<PhaseStep>,
+    /// Paso actual de `sequence`
+    #[serde(default)]
+    pub current_step: usize,
+    #[serde(default)]
+    pub sounds: SoundSettings,
+    #[serde(default)]
+    pub auto_start: AutoStartSettings,
+    #[serde(default)]
+    pub idle: IdleSettings,
+    #[serde(default)]
+    pub suspend: SuspendSettings,
+    #[serde(default)]
+    pub schedule: ScheduleSettings,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paused_by: Option<PauseCause>,
+    /// Ausencia pendiente de resolver
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub away: Option<AwayPeriod>,
+    /// Último perfil aplicado con `useprofile`
+    #[serde(default)]
+    pub active_profile: Option<String>,
+    /// Tarea a la que se apuntan las fases de trabajo completadas
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_task: Option<u32>,
+    /// Perfil que se aplicará al terminar la fase actual
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pending_profile: Option<PendingProfile>,
+    /// Últimos saltos, extensiones y reinicios de fase de la sesión en curso
+    /// (como mucho `MAX_SESSION_EVENTS`)
+    #[serde(default)]
+    pub session_events: Vec<SessionEvent>,
+    /// Bloques de la sesión en curso cuando la agenda los fija; sustituye a
+    /// `cycles` hasta que se reinicia el progreso.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_cycles: Option<u32>,
+    /// Fase en curso en el historial (ver `history::begin_phase`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phase_session: Option<String>,
+    /// Segundos que lleva corriendo la fase en curso, sin contar pausas
+    #[serde(default)]
+    pub phase_elapsed: u32,
+    #[serde(default)]
+    pub server: ServerSettings,
+    /// Nombre de un temporizador secundario
+    #[serde(skip)]
+    pub timer_label: Option<String>,
+    /// Configuración que vive solo en memoria (temporizadores secundarios,
+    /// pruebas): `save_config` no escribe nada.
+    #[serde(skip)]
+    pub in_memory: bool,
+}
+
+/// Límites de cordura para los valores que llegan de los clientes.
+pub const MAX_DURATION_SECS: u32 = 8 * 60 * 60;
+pub const MAX_CYCLES: u32 = 100;
+pub const MAX_SEQUENCE_STEPS: usize = 100;
+pub const MAX_SCHEDULED_SESSIONS: usize = 50;
+/// Eventos que se conservan en `session_events`; el registro completo está
+/// en el historial.
+pub const MAX_SESSION_EVENTS: usize = 20;
+
+/// Cambios parciales de configuración: solo se aplican los campos presentes.
+/// El estado de ejecución (`time_left`, `current_phase`, ...) no se puede
+/// tocar por aquí.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigPatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub work_duration: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub break_duration: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub long_break_duration: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cycles: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub long_break_interval: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub final_long_break: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repeat: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub daily_goal: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sequence: Option<Vec<PhaseStep>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<TimerMode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flow: Option<FlowSettings>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sounds: Option<SoundSettings>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_start: Option<AutoStartSettings>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub idle: Option<IdleSettings>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suspend: Option<SuspendSettings>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<ScheduleSettings>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server: Option<ServerSettings>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    pub field: &'static str,
+    pub message: String,
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
 }
 
 pub fn config_path() -> String {
-    if is_debug() {
-        "pomodoro_config.json".to_string()
-    } else if let Some(proj_dir) = dirs::config_dir() {
-        let full_path = proj_dir.join("global_pomodoro");
-        std::fs::create_dir_all(&full_path).ok();
-        full_path.join("pomodoro_config.json").to_string_lossy().to_string()
-    } else {
-        "pomodoro_config.json".to_string()
+    data_file("pomodoro_config.json")
+}
+
+/// Ruta de un archivo de datos: junto al binario en desarrollo y en
+/// `~/.config/global_pomodoro` en producción.
+pub fn data_file(name: &str) -> String {
+    file_in(dirs::config_dir(), name)
+}
+
+/// Como `data_file`, pero para archivos de ejecución (el socket), que van
+/// en `$XDG_RUNTIME_DIR` si existe.
+pub fn runtime_file(name: &str) -> String {
+    file_in(dirs::runtime_dir().or_else(dirs::config_dir), name)
+}
+
+fn file_in(base: Option<std::path::PathBuf>, name: &str) -> String {
+    match base {
+        Some(base) if !is_debug() => {
+            let full_path = base.join("global_pomodoro");
+            std::fs::create_dir_all(&full_path).ok();
+            full_path.join(name).to_string_lossy().to_string()
+        }
+        _ => name.to_string(),
     }
 }
 
+/// Los sonidos se buscan en la carpeta de sonidos; no se aceptan rutas.
+fn is_sound_file_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains('/') && name != ".."
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_daily_goal() -> u32 {
+    8
+}
+
 fn is_debug() -> bool {
     cfg!(debug_assertions) || env::var("DEV_MODE").is_ok()
 }
 
+impl Default for PomodoroStateConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl PomodoroStateConfig {
     pub fn new() -> Self {
         Self {
@@ -50,10 +533,411 @@ impl PomodoroStateConfig {
             long_break_duration: 10 * 60,
             cycles: 4,
             current_cycle: 0,
-            is_running: true,
+            legacy_running: false,
             long_break_interval: 2,
+            final_long_break: true,
+            repeat: false,
+            daily_goal: default_daily_goal(),
             time_left: 25 * 60,
             current_phase: Phase::Work,
+            state: TimerState::Idle,
+            mode: TimerMode::Pomodoro,
+            flow: FlowSettings::default(),
+            flow_elapsed: 0,
+            flow_break: 0,
+            sequence: Vec::new(),
+            current_step: 0,
+            sounds: SoundSettings::default(),
+            auto_start: AutoStartSettings::default(),
+            idle: IdleSettings::default(),
+            suspend: SuspendSettings::default(),
+            schedule: ScheduleSettings::default(),
+            paused_by: None,
+            away: None,
+            active_profile: None,
+            active_task: None,
+            pending_profile: None,
+            session_events: Vec::new(),
+            phase_session: None,
+            phase_elapsed: 0,
+            session_cycles: None,
+            server: ServerSettings::default(),
+            timer_label: None,
+            in_memory: false,
+        }
+    }
+
+    /// Temporizador secundario de una sola fase de `duration` segundos.
+    pub fn side_timer(label: &str, duration: u32) -> Self {
+        let mut config = Self::new();
+        config.cycles = 1;
+        config.sequence = vec![PhaseStep {
+            name: format!("⏲ {}", label),
+            kind: Phase::Work,
+            duration,
+            block_sites: false,
+            sound: None,
+            notification: Some("Temporizador en marcha".to_string()),
+            auto_start: None,
+        }];
+        config.timer_label = Some(label.to_string());
+        config.in_memory = true;
+        config.reset_progress();
+        config
+    }
+
+    /// Comprueba que la configuración se puede ejecutar sin sorpresas:
+    /// `long_break_interval` se usa como divisor en `next_phase`, y una
+    /// duración cero encadenaría fases sin pausa.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let durations = [
+            ("work_duration", self.work_duration),
+            ("break_duration", self.break_duration),
+            ("long_break_duration", self.long_break_duration),
+        ];
+        for (field, value) in durations {
+            if value == 0 || value > MAX_DURATION_SECS {
+                return Err(ValidationError {
+                    field,
+                    message: format!("must be between 1 and {} seconds", MAX_DURATION_SECS),
+                });
+            }
+        }
+
+        let counts = [
+            ("cycles", self.cycles),
+            ("long_break_interval", self.long_break_interval),
+            ("daily_goal", self.daily_goal),
+        ];
+        for (field, value) in counts {
+            if value == 0 || value > MAX_CYCLES {
+                return Err(ValidationError {
+                    field,
+                    message: format!("must be between 1 and {}", MAX_CYCLES),
+                });
+            }
+        }
+
+        if self.sequence.len() > MAX_SEQUENCE_STEPS {
+            return Err(ValidationError {
+                field: "sequence",
+                message: format!("must have at most {} steps", MAX_SEQUENCE_STEPS),
+            });
+        }
+        for (index, step) in self.sequence.iter().enumerate() {
+            let problem = if step.name.trim().is_empty() {
+                Some("name must not be empty".to_string())
+            } else if step.kind == Phase::Idle {
+                Some("kind must be Work, ShortBreak or LongBreak".to_string())
+            } else if step.duration == 0 || step.duration > MAX_DURATION_SECS {
+                Some(format!("duration must be between 1 and {} seconds", MAX_DURATION_SECS))
+            } else if step.sound.as_deref().is_some_and(|sound| !is_sound_file_name(sound)) {
+                Some("sound must be a file name inside the sounds folder".to_string())
+            } else {
+                None
+            };
+            if let Some(problem) = problem {
+                return Err(ValidationError {
+                    field: "sequence",
+                    message: format!("step {}: {}", index, problem),
+                });
+            }
+        }
+        if !self.sequence.is_empty() && self.current_step >= self.sequence.len() {
+            return Err(ValidationError {
+                field: "current_step",
+                message: format!("must be lower than the number of steps ({})", self.sequence.len()),
+            });
+        }
+
+        if self.flow.break_percent == 0 || self.flow.break_percent > 100 {
+            return Err(ValidationError {
+                field: "flow.break_percent",
+                message: "must be between 1 and 100".into(),
+            });
+        }
+
+        if self.auto_start.nag_minutes.is_some_and(|minutes| minutes == 0 || minutes > MAX_DURATION_SECS / 60) {
+            return Err(ValidationError {
+                field: "auto_start.nag_minutes",
+                message: format!("must be between 1 and {}", MAX_DURATION_SECS / 60),
+            });
+        }
+
+        if self.idle.threshold_secs == 0 || self.idle.threshold_secs > MAX_DURATION_SECS {
+            return Err(ValidationError {
+                field: "idle.threshold_secs",
+                message: format!("must be between 1 and {} seconds", MAX_DURATION_SECS),
+            });
+        }
+        if matches!(&self.idle.source, IdleSourceSettings::Command { command } if command.trim().is_empty()) {
+            return Err(ValidationError {
+                field: "idle.source.command",
+                message: "must not be empty".into(),
+            });
+        }
+
+        self.validate_schedule()?;
+
+        let sounds = [&self.sounds.work, &self.sounds.short_break, &self.sounds.long_break, &self.sounds.finished];
+        if sounds.into_iter().flatten().any(|sound| !is_sound_file_name(sound)) {
+            return Err(ValidationError {
+                field: "sounds",
+                message: "must be file names inside the sounds folder".into(),
+            });
+        }
+
+        if self.server.ping_interval_secs == 0 {
+            return Err(ValidationError {
+                field: "server.ping_interval_secs",
+                message: "must be greater than 0".into(),
+            });
+        }
+        if self.server.pong_timeout_secs < self.server.ping_interval_secs {
+            return Err(ValidationError {
+                field: "server.pong_timeout_secs",
+                message: "must be at least ping_interval_secs".into(),
+            });
+        }
+        if self.server.client_queue_size == 0 {
+            return Err(ValidationError {
+                field: "server.client_queue_size",
+                message: "must be greater than 0".into(),
+            });
+        }
+
+        Ok(())
+    }
+
+    fn validate_schedule(&self) -> Result<(), ValidationError> {
+        let schedule = &self.schedule;
+        if schedule.sessions.len() > MAX_SCHEDULED_SESSIONS {
+            return Err(ValidationError {
+                field: "schedule.sessions",
+                message: format!("must have at most {} sessions", MAX_SCHEDULED_SESSIONS),
+            });
+        }
+        if schedule.end_of_day.as_deref().is_some_and(|time| parse_clock_time(time).is_none()) {
+            return Err(ValidationError {
+                field: "schedule.end_of_day",
+                message: "must be a time like 18:00".into(),
+            });
+        }
+        for (index, session) in schedule.sessions.iter().enumerate() {
+            let start = parse_clock_time(&session.start);
+            let end = session.end.as_deref().map(parse_clock_time);
+            let problem = if session.days.is_empty() {
+                Some("days must not be empty".to_string())
+            } else if start.is_none() {
+                Some("start must be a time like 09:00".to_string())
+            } else if matches!(end, Some(None)) {
+                Some("end must be a time like 12:00".to_string())
+            } else if end.flatten().is_some_and(|end| Some(end) <= start) {
+                Some("end must be later than start".to_string())
+            } else if session.cycles.is_some_and(|cycles| cycles == 0 || cycles > MAX_CYCLES) {
+                Some(format!("cycles must be between 1 and {}", MAX_CYCLES))
+            } else {
+                None
+            };
+            if let Some(problem) = problem {
+                return Err(ValidationError {
+                    field: "schedule.sessions",
+                    message: format!("session {}: {}", index, problem),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// `validate` para cambios que llegan de un cliente. `idle.source` puede
+    /// ejecutar un comando, así que solo se cambia editando el archivo.
+    pub fn validate_change(&self, previous: &Self) -> Result<(), ValidationError> {
+        self.validate()?;
+        if self.idle.source != previous.idle.source {
+            return Err(ValidationError {
+                field: "idle.source",
+                message: "can only be changed in the config file".into(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Todos los ajustes como un parche completo, sin el estado de ejecución.
+    /// `updateconfig` aplica así la configuración recibida, para que un estado
+    /// devuelto tal cual no pise la fase ni el tiempo en curso.
+    pub fn settings(&self) -> ConfigPatch {
+        ConfigPatch {
+            work_duration: Some(self.work_duration),
+            break_duration: Some(self.break_duration),
+            long_break_duration: Some(self.long_break_duration),
+            cycles: Some(self.cycles),
+            long_break_interval: Some(self.long_break_interval),
+            final_long_break: Some(self.final_long_break),
+            repeat: Some(self.repeat),
+            daily_goal: Some(self.daily_goal),
+            sequence: Some(self.sequence.clone()),
+            mode: Some(self.mode),
+            flow: Some(self.flow.clone()),
+            sounds: Some(self.sounds.clone()),
+            auto_start: Some(self.auto_start.clone()),
+            idle: Some(self.idle.clone()),
+            suspend: Some(self.suspend.clone()),
+            schedule: Some(self.schedule.clone()),
+            server: Some(self.server.clone()),
+        }
+    }
+
+    /// Aplica un `ConfigPatch`. Si la fase actual se acorta por debajo del
+    /// tiempo que le queda, `time_left` se recorta a la nueva duración.
+    pub fn apply_patch(&mut self, patch: &ConfigPatch) {
+        if let Some(value) = patch.work_duration {
+            self.work_duration = value;
+        }
+        if let Some(value) = patch.break_duration {
+            self.break_duration = value;
+        }
+        if let Some(value) = patch.long_break_duration {
+            self.long_break_duration = value;
+        }
+        if let Some(value) = patch.cycles {
+            self.cycles = value;
+        }
+        if let Some(value) = patch.long_break_interval {
+            self.long_break_interval = value;
+        }
+        if let Some(value) = patch.final_long_break {
+            self.final_long_break = value;
+        }
+        if let Some(value) = patch.repeat {
+            self.repeat = value;
+        }
+        if let Some(value) = patch.daily_goal {
+            self.daily_goal = value;
+        }
+        if let Some(server) = &patch.server {
+            self.server = server.clone();
+        }
+        if let Some(sounds) = &patch.sounds {
+            self.sounds = sounds.clone();
+        }
+        if let Some(auto_start) = &patch.auto_start {
+            self.auto_start = auto_start.clone();
+        }
+        if let Some(idle) = &patch.idle {
+            self.idle = idle.clone();
+        }
+        if let Some(suspend) = &patch.suspend {
+            self.suspend = suspend.clone();
+        }
+        if let Some(schedule) = &patch.schedule {
+            self.schedule = schedule.clone();
+        }
+        if let Some(mode) = patch.mode {
+            self.mode = mode;
+        }
+        if let Some(flow) = &patch.flow {
+            self.flow = flow.clone();
+        }
+        if let Some(sequence) = &patch.sequence {
+            self.sequence = sequence.clone();
+            if self.current_step >= self.sequence.len() {
+                self.current_step = 0;
+            }
+            if let Some(step) = self.sequence.get(self.current_step) {
+                if self.current_phase != Phase::Idle {
+                    self.current_phase = step.kind;
+                }
+            }
+        }
+
+        if self.current_phase != Phase::Idle {
+            self.time_left = self.time_left.min(self.current_duration());
+        }
+    }
+
+    fn phase_duration(&self, phase: Phase) -> u32 {
+        match phase {
+            Phase::Work => self.work_duration,
+            Phase::ShortBreak => self.break_duration,
+            Phase::LongBreak => self.long_break_duration,
+            Phase::Idle => 0,
+        }
+    }
+
+    /// Paso en curso: el de `sequence`, o uno del patrón clásico si no hay
+    /// secuencia. `None` cuando el ciclo ha terminado.
+    pub fn current_step(&self) -> Option<PhaseStep> {
+        if self.current_phase == Phase::Idle {
+            return None;
+        }
+        if self.mode == TimerMode::Flow {
+            return Some(self.flow_step());
+        }
+        if self.sequence.is_empty() {
+            let name = match self.current_phase {
+                Phase::Work => "🔨 Trabajando...",
+                Phase::ShortBreak => "☕ Pausa corta...",
+                _ => "🛌 Pausa larga...",
+            };
+            return Some(PhaseStep {
+                name: name.to_string(),
+                kind: self.current_phase,
+                duration: self.phase_duration(self.current_phase),
+                block_sites: false,
+                sound: self.sounds.for_phase(self.current_phase).cloned(),
+                notification: None,
+                auto_start: None,
+            });
+        }
+        self.sequence.get(self.current_step).cloned().map(|mut step| {
+            if step.sound.is_none() {
+                step.sound = self.sounds.for_phase(step.kind).cloned();
+            }
+            step
+        })
+    }
+
+    /// En modo flujo el trabajo no tiene duración (cuenta hacia arriba) y
+    /// cualquier pausa dura lo calculado al terminarlo.
+    fn flow_step(&self) -> PhaseStep {
+        let (name, duration, block_sites) = if self.current_phase == Phase::Work {
+            ("🌊 En flujo...", 0, self.flow.block_sites)
+        } else {
+            ("☕ Pausa...", self.flow_break, false)
+        };
+        PhaseStep {
+            name: name.to_string(),
+            kind: self.current_phase,
+            duration,
+            block_sites,
+            sound: self.sounds.for_phase(self.current_phase).cloned(),
+            notification: None,
+            auto_start: None,
+        }
+    }
+
+    /// Si la fase actual empieza sola al terminar la anterior.
+    pub fn auto_starts(&self) -> bool {
+        self.current_step()
+            .is_none_or(|step| step.auto_start.unwrap_or(self.auto_start.for_phase(step.kind)))
+    }
+
+    /// Fase de trabajo en modo flujo: cuenta hacia arriba.
+    pub fn is_counting_up(&self) -> bool {
+        self.mode == TimerMode::Flow && self.current_phase == Phase::Work
+    }
+
+    pub fn current_duration(&self) -> u32 {
+        self.current_step().map_or(0, |step| step.duration)
+    }
+
+    /// Tiempo que le queda a la fase actual. `time_left == 0` significa que
+    /// la fase aún no ha empezado a contar, así que le queda entera.
+    pub fn remaining(&self) -> u32 {
+        if self.time_left == 0 {
+            self.current_duration()
+        } else {
+            self.time_left
         }
     }
 
@@ -64,8 +948,7 @@ impl PomodoroStateConfig {
             let mut reader = BufReader::new(file);
             let mut contents = String::new();
             reader.read_to_string(&mut contents).await.map_err(serde_json::Error::io)?;
-            let config = serde_json::from_str(&contents)?;
-            Ok(config)
+            Self::from_saved(&contents)
         } else {
             let config = PomodoroStateConfig::new();
             config.save_config().await?;
@@ -73,25 +956,99 @@ impl PomodoroStateConfig {
         }
     }
 
+    pub fn is_running(&self) -> bool {
+        self.state == TimerState::Running
+    }
+
+    /// Lee una configuración guardada y la deja lista para un servidor
+    /// recién arrancado.
+    pub fn from_saved(contents: &str) -> Result<Self, Error> {
+        let mut config: PomodoroStateConfig = serde_json::from_str(contents)?;
+        config.recover_after_restart();
+        Ok(config)
+    }
+
+    /// Cambia el estado del motor. Una pausa que no es `Paused` ya no es
+    /// del servidor, así que se olvida su causa.
+    pub fn set_state(&mut self, state: TimerState) {
+        self.state = state;
+        if state != TimerState::Paused {
+            self.paused_by = None;
+        }
+    }
+
+    /// Al arrancar el servidor no hay tarea del temporizador, así que lo que
+    /// estaba en marcha (o un archivo antiguo sin `state`) queda en pausa.
+    fn recover_after_restart(&mut self) {
+        if self.current_phase == Phase::Idle {
+            self.set_state(TimerState::Finished);
+        } else if self.state == TimerState::Running || self.legacy_running {
+            self.set_state(TimerState::Paused);
+        }
+        self.legacy_running = false;
+    }
+
+    /// Escribe a un archivo temporal y lo renombra, para que abortar la tarea
+    /// del temporizador a mitad de guardado nunca deje el archivo truncado.
     pub async fn save_config(&self) -> Result<(), Error> {
+        if let Some(tmp_path) = self.stage_config().await? {
+            tokio::fs::rename(&tmp_path, config_path()).await.map_err(serde_json::Error::io)?;
+        }
+        Ok(())
+    }
+
+    /// Escribe la configuración en un fichero temporal y devuelve su ruta sin
+    /// renombrarlo. `None` si la configuración solo vive en memoria.
+    pub async fn stage_config(&self) -> Result<Option<String>, Error> {
+        if self.in_memory {
+            return Ok(None);
+        }
         let json = serde_json::to_string_pretty(self)?;
-        let path = config_path();
+        let tmp_path = format!("{}.tmp", config_path());
         let mut file = OpenOptions::new()
             .write(true)
             .create(true)
             .truncate(true)
-            .open(&path)
+            .open(&tmp_path)
             .await
             .map_err(serde_json::Error::io)?;
         file.write_all(json.as_bytes()).await.map_err(serde_json::Error::io)?;
-        Ok(())
+        Ok(Some(tmp_path))
     }
 
-    pub async fn reset_mut(&mut self) -> Result<(), Error> {
+    /// Anota `event` en `session_events`, descartando los más antiguos.
+    pub fn push_event(&mut self, event: SessionEvent) {
+        self.session_events.push(event);
+        let excess = self.session_events.len().saturating_sub(MAX_SESSION_EVENTS);
+        self.session_events.drain(..excess);
+    }
+
+    /// Bloques de trabajo de la sesión en curso.
+    pub fn planned_cycles(&self) -> u32 {
+        self.session_cycles.unwrap_or(self.cycles)
+    }
+
+    /// Vuelve al principio del ciclo sin tocar los ajustes.
+    pub fn reset_progress(&mut self) {
         self.current_cycle = 0;
-        self.current_phase = Phase::Work;
-        self.time_left = self.work_duration;
-        self.is_running = false;
+        self.current_step = 0;
+        self.flow_elapsed = 0;
+        self.flow_break = 0;
+        self.current_phase = match self.mode {
+            TimerMode::Flow => Phase::Work,
+            TimerMode::Pomodoro => self.sequence.first().map_or(Phase::Work, |step| step.kind),
+        };
+        self.time_left = self.current_duration();
+        self.session_events.clear();
+        self.phase_session = None;
+        self.phase_elapsed = 0;
+        self.session_cycles = None;
+        self.away = None;
+        self.set_state(TimerState::Idle);
+    }
+
+    pub async fn reset_mut(&mut self) -> Result<(), Error> {
+        self.reset_progress();
         self.save_config().await
     }
 
@@ -100,3 +1057,78 @@ impl PomodoroStateConfig {
         self.save_config().await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::SessionEventKind;
+
+    fn saved(edit: impl FnOnce(&mut PomodoroStateConfig)) -> String {
+        let mut cfg = PomodoroStateConfig::new();
+        edit(&mut cfg);
+        serde_json::to_string(&cfg).unwrap()
+    }
+
+    #[test]
+    fn session_events_keep_only_the_latest() {
+        let mut cfg = PomodoroStateConfig::new();
+        for seconds in 0..MAX_SESSION_EVENTS as u32 + 5 {
+            cfg.push_event(SessionEvent { at: 0, phase: Phase::Work, kind: SessionEventKind::Extended { seconds } });
+        }
+        assert_eq!(cfg.session_events.len(), MAX_SESSION_EVENTS);
+        assert_eq!(cfg.session_events[0].kind, SessionEventKind::Extended { seconds: 5 });
+    }
+
+    #[test]
+    fn running_timer_is_paused_after_a_crash() {
+        let json = saved(|cfg| {
+            cfg.set_state(TimerState::Running);
+            cfg.current_cycle = 2;
+            cfg.time_left = 321;
+        });
+        let cfg = PomodoroStateConfig::from_saved(&json).unwrap();
+        assert_eq!(cfg.state, TimerState::Paused);
+        assert!(!cfg.is_running());
+        assert_eq!((cfg.current_cycle, cfg.time_left), (2, 321));
+    }
+
+    #[test]
+    fn old_file_without_state_uses_is_running() {
+        let json = saved(|_| {}).replace("\"state\":\"Idle\"", "\"is_running\":true");
+        assert_eq!(PomodoroStateConfig::from_saved(&json).unwrap().state, TimerState::Paused);
+    }
+
+    #[test]
+    fn finished_cycle_stays_finished() {
+        let json = saved(|cfg| {
+            cfg.current_phase = Phase::Idle;
+            cfg.set_state(TimerState::Running);
+        });
+        assert_eq!(PomodoroStateConfig::from_saved(&json).unwrap().state, TimerState::Finished);
+    }
+
+    #[test]
+    fn validate_rejects_zero_interval_and_bad_schedule() {
+        let mut cfg = PomodoroStateConfig::new();
+        assert!(cfg.validate().is_ok());
+        cfg.long_break_interval = 0;
+        assert_eq!(cfg.validate().unwrap_err().field, "long_break_interval");
+
+        let mut cfg = PomodoroStateConfig::new();
+        cfg.schedule.sessions.push(ScheduledSession {
+            days: vec![Weekday::Mon],
+            start: "9am".into(),
+            cycles: None,
+            end: None,
+        });
+        assert_eq!(cfg.validate().unwrap_err().field, "schedule.sessions");
+    }
+
+    #[test]
+    fn patch_clamps_time_left_to_shorter_phase() {
+        let mut cfg = PomodoroStateConfig::new();
+        cfg.time_left = 20 * 60;
+        cfg.apply_patch(&ConfigPatch { work_duration: Some(10 * 60), ..Default::default() });
+        assert_eq!(cfg.time_left, 10 * 60);
+    }
+}
@@ -0,0 +1,386 @@
+use std::collections::{BTreeMap, HashSet};
+
+use chrono::{DateTime, Local, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{Phase, ValidationError};
+use crate::history::{HistoryEvent, HistoryFilter, HistoryRange, HistoryRecord, PhaseOutcome};
+use crate::tasks::Task;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Json,
+    /// iCalendar: un VEVENT por fase de trabajo
+    Ics,
+}
+
+/// Una fase cerrada del historial, tal como se exporta. `id` es el
+/// `session` del historial y sirve para no importar dos veces lo mismo.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct CompletedSession {
+    pub id: String,
+    pub phase: Phase,
+    /// Marcas de tiempo Unix, en segundos
+    pub start: i64,
+    pub end: i64,
+    pub planned: u32,
+    pub actual: u32,
+    pub outcome: PhaseOutcome,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task: Option<u32>,
+    /// Solo informativo: al importar no se usa
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task_title: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+}
+
+const CSV_HEADER: &str = "id,phase,start,end,planned,actual,outcome,task,task_title,tags,profile";
+
+/// Fases cerradas cuyo fin cae en `range` y pasa `filter`, en orden.
+pub fn completed_sessions(
+    records: &[HistoryRecord],
+    range: HistoryRange,
+    filter: &HistoryFilter,
+    tasks: &[Task],
+) -> Vec<CompletedSession> {
+    let mut starts: BTreeMap<&str, i64> = BTreeMap::new();
+    for record in records {
+        if let HistoryEvent::Started { .. } = record.event {
+            starts.entry(record.session.as_str()).or_insert(record.at);
+        }
+    }
+
+    let mut sessions: Vec<CompletedSession> = records
+        .iter()
+        .filter(|record| range.contains(record.at) && filter.matches(record))
+        .filter_map(|record| match record.event {
+            HistoryEvent::Ended { planned, actual, outcome } => Some(CompletedSession {
+                id: record.session.clone(),
+                phase: record.phase,
+                start: starts.get(record.session.as_str()).copied().unwrap_or(record.at - i64::from(actual)),
+                end: record.at,
+                planned,
+                actual,
+                outcome,
+                task: record.task,
+                task_title: record
+                    .task
+                    .and_then(|id| tasks.iter().find(|task| task.id == id))
+                    .map(|task| task.title.clone()),
+                tags: record.tags.clone(),
+                profile: record.profile.clone(),
+            }),
+            _ => None,
+        })
+        .collect();
+    sessions.sort_by_key(|session| session.start);
+    sessions
+}
+
+pub fn export(sessions: &[CompletedSession], format: ExportFormat) -> String {
+    match format {
+        ExportFormat::Csv => to_csv(sessions),
+        ExportFormat::Json => serde_json::to_string_pretty(sessions).unwrap_or_default(),
+        ExportFormat::Ics => to_ics(sessions),
+    }
+}
+
+/// Las fechas van en RFC 3339 con la hora local, que las hojas de cálculo
+/// entienden; las etiquetas, separadas por `;`.
+fn to_csv(sessions: &[CompletedSession]) -> String {
+    let mut csv = format!("{}\n", CSV_HEADER);
+    for session in sessions {
+        let fields = [
+            session.id.clone(),
+            format!("{:?}", session.phase),
+            local_rfc3339(session.start),
+            local_rfc3339(session.end),
+            session.planned.to_string(),
+            session.actual.to_string(),
+            format!("{:?}", session.outcome),
+            session.task.map(|id| id.to_string()).unwrap_or_default(),
+            session.task_title.clone().unwrap_or_default(),
+            session.tags.join(";"),
+            session.profile.clone().unwrap_or_default(),
+        ];
+        let line: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&line.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+fn local_rfc3339(at: i64) -> String {
+    Local.timestamp_opt(at, 0).single().map(|at| at.to_rfc3339()).unwrap_or_else(|| at.to_string())
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Cada fase de trabajo es un bloque en el calendario. El `UID` es el id de
+/// la fase, así que volver a importar el archivo actualiza los bloques en
+/// lugar de duplicarlos.
+fn to_ics(sessions: &[CompletedSession]) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//global_pomodoro//ES".to_string(),
+    ];
+    for session in sessions.iter().filter(|session| session.phase == Phase::Work) {
+        let summary = match &session.task_title {
+            Some(title) => format!("🍅 {}", title),
+            None => "🍅 Pomodoro".to_string(),
+        };
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}@global_pomodoro", session.id));
+        lines.push(format!("DTSTAMP:{}", ics_time(session.end)));
+        lines.push(format!("DTSTART:{}", ics_time(session.start)));
+        lines.push(format!("DTEND:{}", ics_time(session.end)));
+        lines.push(format!("SUMMARY:{}", ics_text(&summary)));
+        if !session.tags.is_empty() {
+            let tags: Vec<String> = session.tags.iter().map(|tag| ics_text(tag)).collect();
+            lines.push(format!("CATEGORIES:{}", tags.join(",")));
+        }
+        lines.push(format!("DESCRIPTION:{:?}\\, {} s de {} s", session.outcome, session.actual, session.planned));
+        lines.push("END:VEVENT".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+    lines.iter().map(|line| fold_ics_line(line)).collect::<Vec<_>>().concat()
+}
+
+fn ics_time(at: i64) -> String {
+    Utc.timestamp_opt(at, 0).single().map(|at| at.format("%Y%m%dT%H%M%SZ").to_string()).unwrap_or_default()
+}
+
+fn ics_text(text: &str) -> String {
+    text.replace('\\', "\\\\").replace(';', "\\;").replace(',', "\\,").replace('\n', "\\n")
+}
+
+/// Las líneas de iCalendar no pasan de 75 bytes: el resto sigue en líneas
+/// que empiezan por un espacio. Todas terminan en CRLF.
+fn fold_ics_line(line: &str) -> String {
+    let mut folded = String::new();
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            width = 1;
+        }
+        folded.push(c);
+        width += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+/// Lee un archivo exportado en JSON o CSV. El iCalendar no guarda lo
+/// suficiente para reconstruir el historial.
+pub fn parse(content: &str, format: ExportFormat) -> Result<Vec<CompletedSession>, ValidationError> {
+    let invalid = |message: String| ValidationError { field: "sessions", message };
+    match format {
+        ExportFormat::Json => serde_json::from_str(content).map_err(|e| invalid(e.to_string())),
+        ExportFormat::Csv => parse_csv(content).map_err(invalid),
+        ExportFormat::Ics => Err(invalid("iCalendar files cannot be imported".to_string())),
+    }
+}
+
+fn parse_csv(content: &str) -> Result<Vec<CompletedSession>, String> {
+    let mut rows = csv_rows(content).into_iter();
+    match rows.next() {
+        Some(header) if header.join(",") == CSV_HEADER => {}
+        _ => return Err(format!("the first line must be `{}`", CSV_HEADER)),
+    }
+    rows.enumerate()
+        .filter(|(_, row)| row.iter().any(|field| !field.is_empty()))
+        .map(|(index, row)| csv_session(&row).map_err(|e| format!("row {}: {}", index + 2, e)))
+        .collect()
+}
+
+fn csv_session(row: &[String]) -> Result<CompletedSession, String> {
+    let [id, phase, start, end, planned, actual, outcome, task, task_title, tags, profile] = row else {
+        return Err(format!("expected 11 fields, found {}", row.len()));
+    };
+    let optional = |field: &String| (!field.is_empty()).then(|| field.clone());
+    Ok(CompletedSession {
+        id: id.clone(),
+        phase: serde_json::from_value(serde_json::Value::String(phase.clone())).map_err(|_| "invalid phase")?,
+        start: parse_time(start)?,
+        end: parse_time(end)?,
+        planned: planned.parse().map_err(|_| "invalid planned")?,
+        actual: actual.parse().map_err(|_| "invalid actual")?,
+        outcome: serde_json::from_value(serde_json::Value::String(outcome.clone())).map_err(|_| "invalid outcome")?,
+        task: optional(task).map(|task| task.parse()).transpose().map_err(|_| "invalid task")?,
+        task_title: optional(task_title),
+        tags: tags.split(';').filter(|tag| !tag.is_empty()).map(str::to_string).collect(),
+        profile: optional(profile),
+    })
+}
+
+fn parse_time(value: &str) -> Result<i64, String> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|at| at.timestamp())
+        .map_err(|_| format!("invalid date {}", value))
+}
+
+/// Filas de un CSV con comillas al estilo RFC 4180.
+fn csv_rows(content: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => row.push(std::mem::take(&mut field)),
+            '\r' if !quoted => {}
+            '\n' if !quoted => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            _ => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows
+}
+
+/// Resultado de `import_records`.
+#[derive(Debug, Default)]
+pub struct Import {
+    /// Líneas que hay que añadir al historial
+    pub records: Vec<HistoryRecord>,
+    /// Fases nuevas
+    pub added: u32,
+    /// Fases que ya estaban o venían repetidas
+    pub skipped: u32,
+}
+
+/// Líneas de historial para las fases de `sessions` que aún no están en
+/// `existing` (ni repetidas en el propio archivo).
+pub fn import_records(sessions: &[CompletedSession], existing: &[HistoryRecord]) -> Result<Import, ValidationError> {
+    let mut seen: HashSet<&str> = existing.iter().map(|record| record.session.as_str()).collect();
+    let mut import = Import::default();
+    for session in sessions {
+        if session.id.is_empty() || session.end < session.start || session.phase == Phase::Idle {
+            return Err(ValidationError {
+                field: "sessions",
+                message: format!("invalid session {:?}", session.id),
+            });
+        }
+        if !seen.insert(session.id.as_str()) {
+            import.skipped += 1;
+            continue;
+        }
+        import.added += 1;
+        let record = |at, event| HistoryRecord {
+            at,
+            session: session.id.clone(),
+            phase: session.phase,
+            task: session.task,
+            tags: session.tags.clone(),
+            profile: session.profile.clone(),
+            event,
+        };
+        import.records.push(record(session.start, HistoryEvent::Started { planned: session.planned }));
+        let ended = HistoryEvent::Ended {
+            planned: session.planned,
+            actual: session.actual,
+            outcome: session.outcome,
+        };
+        import.records.push(record(session.end, ended));
+    }
+    Ok(import)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history() -> Vec<HistoryRecord> {
+        let record = |at, session: &str, phase, event| HistoryRecord {
+            at,
+            session: session.to_string(),
+            phase,
+            task: Some(1),
+            tags: vec!["cliente".into()],
+            profile: None,
+            event,
+        };
+        vec![
+            record(1_000, "1000-Work", Phase::Work, HistoryEvent::Started { planned: 1500 }),
+            record(1_100, "1000-Work", Phase::Work, HistoryEvent::Paused),
+            record(2_600, "1000-Work", Phase::Work, HistoryEvent::Ended { planned: 1500, actual: 1500, outcome: PhaseOutcome::Completed }),
+            record(2_600, "2600-ShortBreak", Phase::ShortBreak, HistoryEvent::Started { planned: 300 }),
+            record(2_700, "2600-ShortBreak", Phase::ShortBreak, HistoryEvent::Ended { planned: 300, actual: 100, outcome: PhaseOutcome::Skipped }),
+        ]
+    }
+
+    fn tasks() -> Vec<Task> {
+        vec![Task { id: 1, title: "Informe, \"final\"".into(), estimate: None, pomodoros: 1, done: false, tags: Vec::new() }]
+    }
+
+    #[test]
+    fn sessions_come_from_ended_phases() {
+        let sessions = completed_sessions(&history(), HistoryRange::default(), &HistoryFilter::default(), &tasks());
+        assert_eq!(sessions.len(), 2);
+        assert_eq!((sessions[0].start, sessions[0].end, sessions[0].actual), (1_000, 2_600, 1500));
+        assert_eq!(sessions[0].task_title.as_deref(), Some("Informe, \"final\""));
+        let work = HistoryFilter { phase: Some(Phase::Work), ..Default::default() };
+        assert_eq!(completed_sessions(&history(), HistoryRange::default(), &work, &[]).len(), 1);
+    }
+
+    #[test]
+    fn csv_and_json_round_trip() {
+        let sessions = completed_sessions(&history(), HistoryRange::default(), &HistoryFilter::default(), &tasks());
+        for format in [ExportFormat::Csv, ExportFormat::Json] {
+            assert_eq!(parse(&export(&sessions, format), format).unwrap(), sessions);
+        }
+        assert!(parse("id,phase\n", ExportFormat::Csv).is_err());
+    }
+
+    #[test]
+    fn ics_has_one_event_per_work_phase() {
+        let sessions = completed_sessions(&history(), HistoryRange::default(), &HistoryFilter::default(), &tasks());
+        let ics = export(&sessions, ExportFormat::Ics);
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 1);
+        assert!(ics.contains("UID:1000-Work@global_pomodoro\r\n"));
+        assert!(ics.contains("SUMMARY:🍅 Informe\\, \"final\"\r\n"));
+        assert!(ics.contains("DTSTART:19700101T001640Z\r\n"));
+        assert!(ics.lines().all(|line| line.len() <= 76));
+    }
+
+    #[test]
+    fn import_skips_known_and_repeated_sessions() {
+        let existing = history();
+        let mut sessions = completed_sessions(&existing, HistoryRange::default(), &HistoryFilter::default(), &[]);
+        let mut new = sessions[0].clone();
+        new.id = "5000-Work".into();
+        new.start = 5_000;
+        new.end = 6_500;
+        sessions.push(new.clone());
+        sessions.push(new);
+
+        let import = import_records(&sessions, &existing).unwrap();
+        assert_eq!((import.added, import.skipped), (1, 3));
+        assert_eq!(import.records.len(), 2);
+        assert!(import.records.iter().all(|record| record.session == "5000-Work"));
+    }
+}
@@ -0,0 +1,335 @@
+use std::io::Read;
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use chrono::{Local, NaiveDate, NaiveTime, TimeZone};
+use serde::{Deserialize, Serialize};
+use tokio::{fs, io::AsyncWriteExt};
+
+use crate::clock::Clock;
+use crate::config::{data_file, Phase, PomodoroStateConfig, ValidationError};
+use crate::tasks::task_tags;
+
+/// Ajustes manuales hechos durante la sesión actual.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(tag = "kind")]
+pub enum SessionEventKind {
+    Skipped,
+    Extended { seconds: u32 },
+    Shortened { seconds: u32 },
+    PhaseRestarted,
+    /// Fin de una fase de flujo, con lo trabajado
+    FlowStopped { worked: u32 },
+    /// Equipo suspendido; con `counted` el tiempo se descontó de la fase
+    Suspended { seconds: u32, counted: bool },
+    /// Ausencia resuelta; con `kept: false` se devolvió el tiempo a la fase
+    Away { seconds: u32, kept: bool },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct SessionEvent {
+    /// Marca de tiempo Unix, en segundos
+    pub at: i64,
+    pub phase: Phase,
+    #[serde(flatten)]
+    pub kind: SessionEventKind,
+}
+
+impl SessionEvent {
+    /// `at` viene del `Clock` del servicio, no de la hora del sistema.
+    pub fn new(at: i64, phase: Phase, kind: SessionEventKind) -> Self {
+        Self { at, phase, kind }
+    }
+}
+
+/// Qué le pasó a una fase. Cada línea de `history.jsonl` es un
+/// `HistoryRecord` con uno de estos eventos.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(tag = "event")]
+pub enum HistoryEvent {
+    Started { planned: u32 },
+    Paused,
+    Resumed,
+    /// Salto, extensión, suspensión, ausencia... (ver `SessionEventKind`)
+    Adjusted(SessionEventKind),
+    /// `planned` es la duración de la fase (0 en el trabajo en flujo) y
+    /// `actual` los segundos que corrió de verdad, sin contar pausas
+    Ended { planned: u32, actual: u32, outcome: PhaseOutcome },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum PhaseOutcome {
+    /// Llegó a cero, o en flujo se cerró con `skip`
+    Completed,
+    Skipped,
+    /// Cortada por un reinicio o por el fin de la jornada
+    Interrupted,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct HistoryRecord {
+    /// Marca de tiempo Unix, en segundos
+    pub at: i64,
+    /// Identifica la fase (`<inicio>-<aleatorio>-<fase>`): todas sus líneas lo comparten
+    pub session: String,
+    pub phase: Phase,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    #[serde(flatten)]
+    pub event: HistoryEvent,
+}
+
+pub fn history_path() -> String {
+    data_file("history.jsonl")
+}
+
+/// Empieza una fase nueva en el historial.
+pub async fn begin_phase(cfg: &mut PomodoroStateConfig, clock: &dyn Clock) {
+    let at = clock.now().timestamp();
+    cfg.phase_session = Some(format!("{}-{}-{:?}", at, session_suffix(), cfg.current_phase));
+    cfg.phase_elapsed = 0;
+    let planned = cfg.current_duration();
+    record(cfg, clock, HistoryEvent::Started { planned }).await;
+}
+
+/// Parte aleatoria del id de una fase, para que dos fases que empiezan en el
+/// mismo segundo (un `skip` rápido) no lo compartan. Sin `/dev/urandom`, un
+/// contador del proceso.
+fn session_suffix() -> String {
+    static FALLBACK: AtomicU32 = AtomicU32::new(0);
+    let mut bytes = [0u8; 4];
+    let random = std::fs::File::open("/dev/urandom").and_then(|mut file| file.read_exact(&mut bytes));
+    if random.is_err() {
+        bytes = FALLBACK.fetch_add(1, Ordering::Relaxed).to_be_bytes();
+    }
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Cierra la fase en curso, si la hay.
+pub async fn end_phase(cfg: &mut PomodoroStateConfig, clock: &dyn Clock, outcome: PhaseOutcome) {
+    let event = HistoryEvent::Ended {
+        planned: cfg.current_duration(),
+        actual: cfg.phase_elapsed,
+        outcome,
+    };
+    record(cfg, clock, event).await;
+    cfg.phase_session = None;
+    cfg.phase_elapsed = 0;
+}
+
+/// Añade `event` a la fase en curso. Sin fase empezada, o con una
+/// configuración solo en memoria, no se escribe nada.
+pub async fn record(cfg: &PomodoroStateConfig, clock: &dyn Clock, event: HistoryEvent) {
+    let Some(session) = cfg.phase_session.clone() else {
+        return;
+    };
+    if cfg.in_memory {
+        return;
+    }
+    let tags = match cfg.active_task {
+        Some(id) => task_tags(id).await,
+        None => Vec::new(),
+    };
+    let record = HistoryRecord {
+        at: clock.now().timestamp(),
+        session,
+        phase: cfg.current_phase,
+        task: cfg.active_task,
+        tags,
+        profile: cfg.active_profile.clone(),
+        event,
+    };
+    if let Err(e) = append(&[record]).await {
+        eprintln!("❌ Error al escribir el historial: {}", e);
+    }
+}
+
+/// Añade líneas al final del historial; nunca reescribe las anteriores.
+pub async fn append(records: &[HistoryRecord]) -> std::io::Result<()> {
+    let mut lines = String::new();
+    for record in records {
+        lines.push_str(&serde_json::to_string(record).map_err(std::io::Error::other)?);
+        lines.push('\n');
+    }
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(history_path()).await?;
+    file.write_all(lines.as_bytes()).await
+}
+
+/// Todo el historial. Las líneas que no se entienden se saltan.
+pub async fn read_history() -> Vec<HistoryRecord> {
+    if !Path::new(&history_path()).exists() {
+        return Vec::new();
+    }
+
+    let content = fs::read_to_string(history_path()).await.unwrap_or_default();
+    parse_history(&content)
+}
+
+fn parse_history(content: &str) -> Vec<HistoryRecord> {
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .filter_map(|(index, line)| match serde_json::from_str(line) {
+            Ok(record) => Some(record),
+            Err(e) => {
+                eprintln!("❌ Línea {} del historial ignorada: {}", index + 1, e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Intervalo de días `AAAA-MM-DD`, en hora local y con ambos extremos
+/// incluidos. Sin extremo, no hay límite por ese lado.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HistoryRange {
+    from: Option<i64>,
+    to: Option<i64>,
+}
+
+impl HistoryRange {
+    pub fn parse(from: Option<&str>, to: Option<&str>) -> Result<Self, ValidationError> {
+        let from = from.map(|day| day_start(day, "from")).transpose()?;
+        let to = to
+            .map(|day| {
+                let date = parse_day(day, "to")?;
+                Ok(date.succ_opt().map(local_midnight).unwrap_or(i64::MAX))
+            })
+            .transpose()?;
+        if let (Some(from), Some(to)) = (from, to) {
+            if from >= to {
+                return Err(ValidationError {
+                    field: "to",
+                    message: "must not be before from".to_string(),
+                });
+            }
+        }
+        Ok(Self { from, to })
+    }
+
+    pub fn contains(&self, at: i64) -> bool {
+        self.from.is_none_or(|from| at >= from) && self.to.is_none_or(|to| at < to)
+    }
+}
+
+fn parse_day(day: &str, field: &'static str) -> Result<NaiveDate, ValidationError> {
+    NaiveDate::parse_from_str(day, "%Y-%m-%d").map_err(|_| ValidationError {
+        field,
+        message: "must be a date like 2026-10-19".to_string(),
+    })
+}
+
+fn day_start(day: &str, field: &'static str) -> Result<i64, ValidationError> {
+    parse_day(day, field).map(local_midnight)
+}
+
+fn local_midnight(date: NaiveDate) -> i64 {
+    let midnight = date.and_time(NaiveTime::MIN);
+    match Local.from_local_datetime(&midnight).earliest() {
+        Some(local) => local.timestamp(),
+        None => midnight.and_utc().timestamp(),
+    }
+}
+
+/// Filtro de `history`: solo cuentan los campos presentes.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistoryFilter {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phase: Option<Phase>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub task: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+}
+
+impl HistoryFilter {
+    pub fn matches(&self, record: &HistoryRecord) -> bool {
+        self.phase.is_none_or(|phase| record.phase == phase)
+            && self.task.is_none_or(|task| record.task == Some(task))
+            && self.tag.as_ref().is_none_or(|tag| record.tags.contains(tag))
+            && self.profile.as_ref().is_none_or(|profile| record.profile.as_ref() == Some(profile))
+    }
+}
+
+/// Líneas del historial dentro de `range` que pasan `filter`.
+pub async fn query(range: HistoryRange, filter: &HistoryFilter) -> Vec<HistoryRecord> {
+    read_history()
+        .await
+        .into_iter()
+        .filter(|record| range.contains(record.at) && filter.matches(record))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(at: i64, event: HistoryEvent) -> HistoryRecord {
+        HistoryRecord {
+            at,
+            session: format!("{}-Work", at),
+            phase: Phase::Work,
+            task: Some(3),
+            tags: vec!["cliente".into()],
+            profile: None,
+            event,
+        }
+    }
+
+    #[test]
+    fn records_round_trip_as_single_lines() {
+        let records = vec![
+            record(10, HistoryEvent::Started { planned: 1500 }),
+            record(20, HistoryEvent::Adjusted(SessionEventKind::Extended { seconds: 60 })),
+            record(30, HistoryEvent::Adjusted(SessionEventKind::Skipped)),
+            record(40, HistoryEvent::Ended { planned: 1500, actual: 1560, outcome: PhaseOutcome::Completed }),
+        ];
+        let lines: Vec<String> = records.iter().map(|record| serde_json::to_string(record).unwrap()).collect();
+        assert!(lines[1].contains("\"event\":\"Adjusted\"") && lines[1].contains("\"kind\":\"Extended\""));
+        let content = format!("{}\nno es json\n", lines.join("\n"));
+        assert_eq!(parse_history(&content), records);
+    }
+
+    #[tokio::test]
+    async fn phases_started_in_the_same_second_get_different_ids() {
+        let clock = crate::clock::FakeClock::new(Local::now());
+        let mut cfg = PomodoroStateConfig::new();
+        cfg.in_memory = true;
+        begin_phase(&mut cfg, &clock).await;
+        let first = cfg.phase_session.clone().unwrap();
+        begin_phase(&mut cfg, &clock).await;
+        let second = cfg.phase_session.clone().unwrap();
+        assert_ne!(first, second);
+        assert!(first.ends_with("-Work") && first.starts_with(&clock.now().timestamp().to_string()));
+    }
+
+    #[test]
+    fn range_includes_whole_days() {
+        let range = HistoryRange::parse(Some("2026-10-19"), Some("2026-10-19")).unwrap();
+        let day = NaiveDate::from_ymd_opt(2026, 10, 19).unwrap();
+        let start = local_midnight(day);
+        assert!(range.contains(start));
+        assert!(range.contains(start + 23 * 3600));
+        assert!(!range.contains(start - 1));
+        assert!(!range.contains(local_midnight(day.succ_opt().unwrap())));
+        assert_eq!(HistoryRange::parse(Some("19/10/2026"), None).unwrap_err().field, "from");
+        assert_eq!(HistoryRange::parse(Some("2026-10-20"), Some("2026-10-19")).unwrap_err().field, "to");
+    }
+
+    #[test]
+    fn filter_checks_only_present_fields() {
+        let started = record(10, HistoryEvent::Started { planned: 1500 });
+        assert!(HistoryFilter::default().matches(&started));
+        assert!(HistoryFilter { tag: Some("cliente".into()), task: Some(3), ..Default::default() }.matches(&started));
+        assert!(!HistoryFilter { phase: Some(Phase::ShortBreak), ..Default::default() }.matches(&started));
+        assert!(!HistoryFilter { profile: Some("coding".into()), ..Default::default() }.matches(&started));
+    }
+}
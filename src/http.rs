@@ -0,0 +1,495 @@
+use std::sync::Arc;
+
+use serde::Deserialize;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, watch};
+use tokio::task;
+use tokio_tungstenite::tungstenite::http::{Method, Request, StatusCode};
+
+use crate::auth::{is_origin_allowed, read_allowed_origins, token_from_request, tokens_match};
+use crate::config::PomodoroStateConfig;
+use crate::export::CompletedSession;
+use crate::history::HistoryFilter;
+use crate::protocol::{ApiError, ClientCommand, ResponsePayload};
+use crate::service::PomodoroService;
+
+const MAX_HEADER_LINES: usize = 64;
+/// Longitud máxima de la línea de petición y de cada cabecera, con el salto
+const MAX_LINE_BYTES: usize = 8 * 1024;
+const MAX_BODY_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Deserialize)]
+struct UrlBody {
+    url: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SecondsBody {
+    seconds: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct AwayBody {
+    keep: bool,
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct UseProfileBody {
+    #[serde(default)]
+    immediately: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct AddTaskBody {
+    title: String,
+    #[serde(default)]
+    estimate: Option<u32>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UpdateTaskBody {
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    estimate: Option<u32>,
+    #[serde(default)]
+    done: Option<bool>,
+    #[serde(default)]
+    tags: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SetTaskBody {
+    id: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImportBody {
+    sessions: Vec<CompletedSession>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CreateTimerBody {
+    id: String,
+    #[serde(default)]
+    label: Option<String>,
+    duration: u32,
+}
+
+pub async fn start_http_server(
+    service: PomodoroService,
+    token: Arc<String>,
+    events: broadcast::Sender<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let listener = TcpListener::bind("127.0.0.1:9002").await?;
+    println!("🌐 HTTP Pomodoro API on http://127.0.0.1:9002");
+
+    let mut shutdown = service.shutdown_signal();
+    loop {
+        let (stream, _) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = shutdown.wait_for(|stop| *stop) => return Ok(()),
+        };
+        let service = service.clone();
+        let token = Arc::clone(&token);
+        let events = events.clone();
+        task::spawn(async move {
+            if let Err(e) = handle_http_connection(stream, service, token, events).await {
+                eprintln!("❌ Error en conexión HTTP: {}", e);
+            }
+        });
+    }
+}
+
+async fn handle_http_connection(
+    stream: TcpStream,
+    service: PomodoroService,
+    token: Arc<String>,
+    events: broadcast::Sender<String>,
+) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let request = match read_request(&mut reader).await? {
+        Ok(request) => request,
+        Err((status, error)) => return write_response(reader.get_mut(), status, &error.into()).await,
+    };
+
+    let origin = request.headers().get("origin").and_then(|v| v.to_str().ok());
+    if !is_origin_allowed(origin, &read_allowed_origins()) {
+        let error = ApiError::forbidden("origin not allowed").into();
+        return write_response(reader.get_mut(), StatusCode::FORBIDDEN, &error).await;
+    }
+    let authorized = token_from_request(&request).is_some_and(|given| tokens_match(&token, &given));
+    if !authorized {
+        let error = ApiError::forbidden("authentication required").into();
+        return write_response(reader.get_mut(), StatusCode::UNAUTHORIZED, &error).await;
+    }
+
+    if request.uri().path() == "/events" {
+        if request.method() != Method::GET {
+            let error = ApiError::invalid(format!("method {} not allowed", request.method())).into();
+            return write_response(reader.get_mut(), StatusCode::METHOD_NOT_ALLOWED, &error).await;
+        }
+        return stream_events(reader.get_mut(), events.subscribe(), service.shutdown_signal()).await;
+    }
+
+    let (status, response) = match route(&request) {
+        Ok(command) => {
+            let timer = timer_from_query(&request);
+            let response = service.execute_on(timer.as_deref(), command).await;
+            (status_for(&response), response)
+        }
+        Err((status, error)) => (status, error.into()),
+    };
+    write_response(reader.get_mut(), status, &response).await
+}
+
+fn status_for(response: &ResponsePayload) -> StatusCode {
+    match response {
+        ResponsePayload::Error(ApiError::InvalidCommand { .. } | ApiError::ValidationFailed { .. }) => {
+            StatusCode::BAD_REQUEST
+        }
+        ResponsePayload::Error(ApiError::Forbidden { .. }) => StatusCode::FORBIDDEN,
+        ResponsePayload::Error(ApiError::NotFound { .. }) => StatusCode::NOT_FOUND,
+        ResponsePayload::Error(ApiError::NotRunning | ApiError::AlreadyRunning | ApiError::NotWaiting) => {
+            StatusCode::CONFLICT
+        }
+        ResponsePayload::Error(ApiError::Io { .. }) => StatusCode::INTERNAL_SERVER_ERROR,
+        _ => StatusCode::OK,
+    }
+}
+
+/// Traduce una ruta REST al mismo `ClientCommand` que usa el WebSocket.
+fn route(request: &Request<Vec<u8>>) -> Result<ClientCommand, (StatusCode, ApiError)> {
+    let method = request.method();
+    match request.uri().path() {
+        "/status" if method == Method::GET => Ok(ClientCommand::Status),
+        "/start" if method == Method::POST => Ok(ClientCommand::Start),
+        "/pause" if method == Method::POST => Ok(ClientCommand::Pause),
+        "/resume" if method == Method::POST => Ok(ClientCommand::Resume),
+        "/skip" if method == Method::POST => Ok(ClientCommand::Skip),
+        "/continue" if method == Method::POST => Ok(ClientCommand::Continue),
+        "/away" if method == Method::POST => {
+            let AwayBody { keep } = parse_body(request)?;
+            Ok(ClientCommand::ResolveAway { keep })
+        }
+        "/extend" if method == Method::POST => {
+            let SecondsBody { seconds } = parse_body(request)?;
+            Ok(ClientCommand::Extend { seconds })
+        }
+        "/shorten" if method == Method::POST => {
+            let SecondsBody { seconds } = parse_body(request)?;
+            Ok(ClientCommand::Shorten { seconds })
+        }
+        "/restart-phase" if method == Method::POST => Ok(ClientCommand::RestartPhase),
+        "/config" if method == Method::GET => Ok(ClientCommand::Status),
+        "/config" if method == Method::PUT => {
+            let new_config = Box::new(parse_body::<PomodoroStateConfig>(request)?);
+            Ok(ClientCommand::UpdateConfig { new_config })
+        }
+        "/config" if method == Method::PATCH => Ok(ClientCommand::PatchConfig(parse_body(request)?)),
+        "/blocklist" if method == Method::GET => Ok(ClientCommand::ListBlocked),
+        "/blocklist" if method == Method::POST => {
+            let UrlBody { url } = parse_body(request)?;
+            Ok(ClientCommand::Block { url })
+        }
+        "/blocklist" if method == Method::DELETE => {
+            let UrlBody { url } = parse_body(request)?;
+            Ok(ClientCommand::Unblock { url })
+        }
+        "/batch" if method == Method::POST => {
+            let commands = parse_body::<Vec<ClientCommand>>(request)?;
+            Ok(ClientCommand::Batch { commands })
+        }
+        "/profiles" if method == Method::GET => Ok(ClientCommand::ListProfiles),
+        path if path.starts_with("/profiles/") => route_profile(request, &path["/profiles/".len()..]),
+        "/tasks" if method == Method::GET => Ok(ClientCommand::ListTasks),
+        "/tasks" if method == Method::POST => {
+            let AddTaskBody { title, estimate, tags } = parse_body(request)?;
+            Ok(ClientCommand::AddTask { title, estimate, tags })
+        }
+        "/tasks/active" if method == Method::PUT => {
+            let SetTaskBody { id } = parse_body(request)?;
+            Ok(ClientCommand::SetTask { id })
+        }
+        path if path.starts_with("/tasks/") && path != "/tasks/active" => route_task(request, &path["/tasks/".len()..]),
+        "/history" if method == Method::GET => {
+            let (from, to, filter) = history_query(request)?;
+            Ok(ClientCommand::History { from, to, filter })
+        }
+        "/history/export" if method == Method::GET => {
+            let format = query_param(request, "format").unwrap_or_else(|| "json".to_string());
+            let format = serde_json::from_value(serde_json::Value::String(format))
+                .map_err(|_| (StatusCode::BAD_REQUEST, ApiError::invalid("invalid format in query")))?;
+            let (from, to, filter) = history_query(request)?;
+            Ok(ClientCommand::ExportHistory { format, from, to, filter })
+        }
+        "/history/import" if method == Method::POST => {
+            let ImportBody { sessions } = parse_body(request)?;
+            Ok(ClientCommand::ImportHistory { sessions })
+        }
+        "/stats" if method == Method::GET => {
+            let (from, to, filter) = history_query(request)?;
+            Ok(ClientCommand::Stats { from, to, filter })
+        }
+        "/timers" if method == Method::POST => {
+            let CreateTimerBody { id, label, duration } = parse_body(request)?;
+            Ok(ClientCommand::CreateTimer { id, label, duration })
+        }
+        path if path.starts_with("/timers/") && method == Method::DELETE => {
+            let id = percent_decode(&path["/timers/".len()..])
+                .ok_or_else(|| (StatusCode::BAD_REQUEST, ApiError::invalid("invalid timer id in path")))?;
+            Ok(ClientCommand::RemoveTimer { id })
+        }
+        "/status" | "/start" | "/pause" | "/resume" | "/skip" | "/continue" | "/away" | "/extend" | "/shorten" | "/restart-phase"
+        | "/config" | "/blocklist" | "/batch" | "/profiles" | "/tasks" | "/tasks/active" | "/history" | "/history/export" | "/history/import" | "/stats" | "/timers" => {
+            Err((StatusCode::METHOD_NOT_ALLOWED, ApiError::invalid(format!("method {} not allowed", method))))
+        }
+        path => Err((StatusCode::NOT_FOUND, ApiError::invalid(format!("unknown route {}", path)))),
+    }
+}
+
+/// `/profiles/{name}` y `/profiles/{name}/use`.
+fn route_profile(request: &Request<Vec<u8>>, rest: &str) -> Result<ClientCommand, (StatusCode, ApiError)> {
+    let method = request.method();
+    let (name, action) = match rest.strip_suffix("/use") {
+        Some(name) => (name, "use"),
+        None => (rest, ""),
+    };
+    let name = percent_decode(name)
+        .ok_or_else(|| (StatusCode::BAD_REQUEST, ApiError::invalid("invalid profile name in path")))?;
+
+    match action {
+        "use" if method == Method::POST => {
+            let UseProfileBody { immediately } =
+                if request.body().is_empty() { UseProfileBody::default() } else { parse_body(request)? };
+            Ok(ClientCommand::UseProfile { name, immediately })
+        }
+        "" if method == Method::PUT => Ok(ClientCommand::SaveProfile { name, profile: parse_body(request)? }),
+        "" if method == Method::DELETE => Ok(ClientCommand::DeleteProfile { name }),
+        _ => Err((StatusCode::METHOD_NOT_ALLOWED, ApiError::invalid(format!("method {} not allowed", method)))),
+    }
+}
+
+/// `/tasks/{id}`.
+fn route_task(request: &Request<Vec<u8>>, rest: &str) -> Result<ClientCommand, (StatusCode, ApiError)> {
+    let method = request.method();
+    let id = rest
+        .parse::<u32>()
+        .map_err(|_| (StatusCode::NOT_FOUND, ApiError::invalid(format!("unknown route /tasks/{}", rest))))?;
+    if method == Method::PATCH {
+        let UpdateTaskBody { title, estimate, done, tags } = parse_body(request)?;
+        Ok(ClientCommand::UpdateTask { id, title, estimate, done, tags })
+    } else if method == Method::DELETE {
+        Ok(ClientCommand::DeleteTask { id })
+    } else {
+        Err((StatusCode::METHOD_NOT_ALLOWED, ApiError::invalid(format!("method {} not allowed", method))))
+    }
+}
+
+/// `?timer=<id>` elige un temporizador secundario, como el campo `timer`
+/// del WebSocket.
+fn timer_from_query(request: &Request<Vec<u8>>) -> Option<String> {
+    query_param(request, "timer")
+}
+
+fn query_param(request: &Request<Vec<u8>>, name: &str) -> Option<String> {
+    request
+        .uri()
+        .query()?
+        .split('&')
+        .find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))
+        .and_then(percent_decode)
+}
+
+/// Días y filtro de `/history` y `/stats`:
+/// `?from=2026-10-01&to=2026-10-19&phase=Work&task=3&tag=cliente&profile=coding`
+type HistoryQuery = (Option<String>, Option<String>, HistoryFilter);
+
+fn history_query(request: &Request<Vec<u8>>) -> Result<HistoryQuery, (StatusCode, ApiError)> {
+    let invalid = |name: &str| (StatusCode::BAD_REQUEST, ApiError::invalid(format!("invalid {} in query", name)));
+    let phase = query_param(request, "phase")
+        .map(|phase| serde_json::from_value(serde_json::Value::String(phase)).map_err(|_| invalid("phase")))
+        .transpose()?;
+    let task = query_param(request, "task")
+        .map(|task| task.parse::<u32>().map_err(|_| invalid("task")))
+        .transpose()?;
+    let filter = HistoryFilter {
+        phase,
+        task,
+        tag: query_param(request, "tag"),
+        profile: query_param(request, "profile"),
+    };
+    Ok((query_param(request, "from"), query_param(request, "to"), filter))
+}
+
+/// Decodifica `%XX` en un segmento de ruta (`meetings%20day`).
+fn percent_decode(segment: &str) -> Option<String> {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = segment.get(i + 1..i + 3)?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+fn parse_body<T: for<'de> Deserialize<'de>>(request: &Request<Vec<u8>>) -> Result<T, (StatusCode, ApiError)> {
+    serde_json::from_slice(request.body())
+        .map_err(|e| (StatusCode::BAD_REQUEST, ApiError::invalid(format!("invalid body: {}", e))))
+}
+
+/// Lee una petición HTTP/1.1 sencilla: línea de petición, cabeceras y cuerpo
+/// según `Content-Length`. Si está mal formada o se pasa de los límites,
+/// devuelve el estado y el error con que contestar.
+async fn read_request<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+) -> std::io::Result<Result<Request<Vec<u8>>, (StatusCode, ApiError)>> {
+    let malformed = || (StatusCode::BAD_REQUEST, ApiError::invalid("malformed HTTP request"));
+    let mut line = String::new();
+    if !read_line_limited(reader, &mut line).await? {
+        return Ok(Err((StatusCode::URI_TOO_LONG, ApiError::invalid("request line too long"))));
+    }
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Ok(Err(malformed()));
+    };
+    let mut builder = Request::builder().method(method).uri(target);
+
+    let mut content_length = 0;
+    let mut headers = 0;
+    loop {
+        if !read_line_limited(reader, &mut line).await? {
+            return Ok(Err((StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE, ApiError::invalid("header line too long"))));
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        headers += 1;
+        if headers > MAX_HEADER_LINES {
+            return Ok(Err((StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE, ApiError::invalid("too many headers"))));
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            return Ok(Err(malformed()));
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("content-length") {
+            content_length = match value.parse::<usize>() {
+                Ok(len) if len <= MAX_BODY_BYTES => len,
+                Ok(_) => return Ok(Err((StatusCode::PAYLOAD_TOO_LARGE, ApiError::invalid("body too large")))),
+                Err(_) => return Ok(Err(malformed())),
+            };
+        }
+        builder = builder.header(name, value);
+    }
+
+    let mut body = Vec::with_capacity(content_length);
+    reader.take(content_length as u64).read_to_end(&mut body).await?;
+    if body.len() < content_length {
+        return Ok(Err(malformed()));
+    }
+    Ok(builder.body(body).map_err(|_| malformed()))
+}
+
+/// Lee una línea en `line` sin pasar de `MAX_LINE_BYTES`. Devuelve `false`
+/// si la línea es más larga.
+async fn read_line_limited<R: AsyncBufRead + Unpin>(reader: &mut R, line: &mut String) -> std::io::Result<bool> {
+    line.clear();
+    let read = reader.take(MAX_LINE_BYTES as u64 + 1).read_line(line).await?;
+    Ok(read <= MAX_LINE_BYTES)
+}
+
+/// Reenvía cada `ResponsePayload::Status` emitido como evento SSE hasta que
+/// el cliente se desconecta o el servidor se apaga. Un suscriptor lento se
+/// salta los eventos perdidos.
+async fn stream_events(
+    stream: &mut TcpStream,
+    mut events: broadcast::Receiver<String>,
+    mut shutdown: watch::Receiver<bool>,
+) -> std::io::Result<()> {
+    let head = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: keep-alive\r\n\r\n";
+    stream.write_all(head.as_bytes()).await?;
+
+    loop {
+        let received = tokio::select! {
+            received = events.recv() => received,
+            _ = shutdown.wait_for(|stop| *stop) => break,
+        };
+        match received {
+            Ok(event) => {
+                stream.write_all(format!("data: {}\n\n", event).as_bytes()).await?;
+            }
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+
+    if *shutdown.borrow() {
+        let farewell = serde_json::to_string(&ResponsePayload::ServerShuttingDown).map_err(std::io::Error::other)?;
+        stream.write_all(format!("data: {}\n\n", farewell).as_bytes()).await?;
+    }
+    stream.shutdown().await
+}
+
+async fn write_response(stream: &mut TcpStream, status: StatusCode, payload: &ResponsePayload) -> std::io::Result<()> {
+    let body = serde_json::to_string(payload).map_err(std::io::Error::other)?;
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status.as_u16(),
+        status.canonical_reason().unwrap_or(""),
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn read(raw: &[u8]) -> Result<Request<Vec<u8>>, (StatusCode, ApiError)> {
+        let mut reader = raw;
+        read_request(&mut reader).await.unwrap()
+    }
+
+    fn status(result: Result<Request<Vec<u8>>, (StatusCode, ApiError)>) -> Option<StatusCode> {
+        result.err().map(|(status, _)| status)
+    }
+
+    #[tokio::test]
+    async fn reads_headers_and_body() {
+        let request = read(b"POST /extend HTTP/1.1\r\nContent-Length: 14\r\n\r\n{\"seconds\":60}").await.unwrap();
+        assert_eq!(request.uri().path(), "/extend");
+        assert_eq!(request.body(), br#"{"seconds":60}"#);
+    }
+
+    #[tokio::test]
+    async fn oversized_requests_are_rejected() {
+        let long_header = format!("GET /status HTTP/1.1\r\nX-Pad: {}\r\n\r\n", "a".repeat(MAX_LINE_BYTES));
+        assert_eq!(status(read(long_header.as_bytes()).await), Some(StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE));
+
+        let many_headers = format!("GET /status HTTP/1.1\r\n{}\r\n", "X-Pad: a\r\n".repeat(MAX_HEADER_LINES + 1));
+        assert_eq!(status(read(many_headers.as_bytes()).await), Some(StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE));
+
+        let long_target = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_LINE_BYTES));
+        assert_eq!(status(read(long_target.as_bytes()).await), Some(StatusCode::URI_TOO_LONG));
+
+        let big_body = format!("POST /config HTTP/1.1\r\nContent-Length: {}\r\n\r\n", MAX_BODY_BYTES + 1);
+        assert_eq!(status(read(big_body.as_bytes()).await), Some(StatusCode::PAYLOAD_TOO_LARGE));
+
+        // Un cuerpo más corto de lo anunciado está mal formado
+        let short_body = b"POST /extend HTTP/1.1\r\nContent-Length: 10\r\n\r\n{}";
+        assert_eq!(status(read(short_body).await), Some(StatusCode::BAD_REQUEST));
+    }
+}
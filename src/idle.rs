@@ -0,0 +1,85 @@
+use std::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::config::{IdleSettings, IdleSourceSettings};
+
+/// De dónde sale el tiempo de inactividad del usuario. Las implementaciones
+/// se llaman desde un hilo bloqueante, así que pueden lanzar procesos.
+pub trait IdleSource: Send + Sync {
+    /// Segundos desde la última interacción; `None` si no se pudo saber.
+    fn idle_seconds(&self) -> Option<u64>;
+}
+
+/// Ejecuta un comando que imprime los milisegundos de inactividad, como
+/// `xprintidle` en X11 o un script equivalente en Wayland.
+pub struct CommandIdleSource {
+    pub command: String,
+}
+
+impl IdleSource for CommandIdleSource {
+    fn idle_seconds(&self) -> Option<u64> {
+        let output = Command::new("sh").arg("-c").arg(&self.command).output().ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let millis: u64 = String::from_utf8_lossy(&output.stdout).trim().parse().ok()?;
+        Some(millis / 1000)
+    }
+}
+
+/// Lee `IdleHint` e `IdleSinceHint` de la sesión de logind con `loginctl`.
+pub struct LogindIdleSource;
+
+impl IdleSource for LogindIdleSource {
+    fn idle_seconds(&self) -> Option<u64> {
+        let session = std::env::var("XDG_SESSION_ID").unwrap_or_else(|_| "self".to_string());
+        let output = Command::new("loginctl")
+            .args(["show-session", &session, "-p", "IdleHint", "-p", "IdleSinceHint"])
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let text = String::from_utf8_lossy(&output.stdout);
+        let value = |key: &str| {
+            text.lines()
+                .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+                .map(str::to_string)
+        };
+        if value("IdleHint")? != "yes" {
+            return Some(0);
+        }
+        // Microsegundos desde la época, en tiempo real
+        let since: i64 = value("IdleSinceHint")?.parse().ok()?;
+        let now = chrono::Utc::now().timestamp_micros();
+        Some(((now - since).max(0) / 1_000_000) as u64)
+    }
+}
+
+/// Fuente manual, para pruebas y para programas que ya saben si el usuario
+/// está presente.
+#[derive(Default)]
+pub struct MockIdleSource {
+    seconds: AtomicU64,
+}
+
+impl MockIdleSource {
+    pub fn set(&self, seconds: u64) {
+        self.seconds.store(seconds, Ordering::SeqCst);
+    }
+}
+
+impl IdleSource for MockIdleSource {
+    fn idle_seconds(&self) -> Option<u64> {
+        Some(self.seconds.load(Ordering::SeqCst))
+    }
+}
+
+pub fn source_from_settings(settings: &IdleSettings) -> Option<Arc<dyn IdleSource>> {
+    match &settings.source {
+        IdleSourceSettings::Disabled => None,
+        IdleSourceSettings::Command { command } => Some(Arc::new(CommandIdleSource { command: command.clone() })),
+        IdleSourceSettings::Logind => Some(Arc::new(LogindIdleSource)),
+    }
+}
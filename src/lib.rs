@@ -0,0 +1,21 @@
+pub mod auth;
+pub mod blocked_sites;
+pub mod client;
+pub mod clock;
+pub mod config;
+pub mod export;
+pub mod history;
+pub mod idle;
+pub mod http;
+pub mod messages;
+pub mod pomodoro;
+pub mod profiles;
+pub mod protocol;
+pub mod schedule;
+pub mod server;
+pub mod service;
+pub mod stats;
+pub mod suspend;
+pub mod tasks;
+pub mod unix_socket;
+pub mod utils;
@@ -1,47 +1,37 @@
-mod config;
-mod server;
-mod client;
-mod pomodoro;
-mod utils;
-mod blocked_sites;
-
-use std::sync::{Arc, Mutex};
-use std::fs;
-use std::path::PathBuf;
-
-use crate::config::PomodoroStateConfig;
-use crate::server::start_server;
-use crate::client::send_command;
-use tokio::sync::Mutex as AsyncMutex;
+use global_pomodoro::config::PomodoroStateConfig;
+use global_pomodoro::server::start_server;
+use global_pomodoro::client::send_command;
+use global_pomodoro::service::PomodoroService;
+use std::process::ExitCode;
 
 
 
 #[tokio::main]
-async fn main() {
-    let build_id = format!("{}", chrono::Utc::now());
-    println!("cargo:rustc-env=BUILD_ID={}", build_id);
+async fn main() -> ExitCode {
     let args: Vec<String> = std::env::args().collect();
 
     // 🖥️ Servidor
     if args.len() > 1 && args[1] == "serve" {
         let config_result = PomodoroStateConfig::load_or_create().await;
-        let config = match config_result {
-            Ok(cfg) => Arc::new(AsyncMutex::new(cfg)),
+        let service = match config_result {
+            Ok(cfg) => PomodoroService::new(cfg),
             Err(e) => {
                 eprintln!("❌ Fallo al cargar configuración: {}", e);
-                return;
+                return ExitCode::FAILURE;
             }
         };
-        if let Err(e) = start_server(config).await {
+        if let Err(e) = start_server(service).await {
             eprintln!("❌ Error al iniciar el servidor: {}", e);
+            return ExitCode::FAILURE;
         }
-        return;
+        return ExitCode::SUCCESS;
     }
 
     // 💬 Cliente
     if args.len() > 1 {
-        send_command(&args[1]);
+        send_command(&args[1], &args[2..]);
     } else {
-        println!("ℹ️ Comandos: start | pause | resume | status | serve");
+        println!("ℹ️ Comandos: start | pause | resume | continue | skip | extend <s> | shorten <s> | addtask <título> | settask <id> | history [export|import] | report [--json] | createtimer <id> <s> | status | shutdown | serve\n   --timer <id> dirige el comando a un temporizador secundario");
     }
+    ExitCode::SUCCESS
 }
@@ -0,0 +1,461 @@
+use std::env;
+
+use chrono::{Local, TimeZone};
+
+use crate::config::{PauseCause, Phase, TimerMode, TimerState};
+use crate::history::{HistoryEvent, HistoryRecord, PhaseOutcome, SessionEventKind};
+use crate::protocol::{ApiError, ResponsePayload};
+use crate::stats::Stats;
+use crate::tasks::Task;
+
+/// Idioma de los textos que ve el usuario. El servidor solo envía tipos y
+/// datos; cada frontend (por ejemplo la CLI) los convierte en texto aquí.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Locale {
+    Es,
+    En,
+}
+
+impl Locale {
+    /// Elige el idioma a partir de `LC_ALL`, `LC_MESSAGES` o `LANG`; por
+    /// defecto, español.
+    pub fn from_env() -> Self {
+        let lang = ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default();
+        if lang.starts_with("en") {
+            Locale::En
+        } else {
+            Locale::Es
+        }
+    }
+}
+
+pub fn render(payload: &ResponsePayload, locale: Locale) -> String {
+    let es = locale == Locale::Es;
+    match payload {
+        ResponsePayload::Started => pick(es, "✅ Temporizador iniciado", "✅ Timer started"),
+        ResponsePayload::Paused => pick(es, "⏸ Temporizador en pausa", "⏸ Timer paused"),
+        ResponsePayload::Resumed => pick(es, "▶️ Temporizador reanudado", "▶️ Timer resumed"),
+        ResponsePayload::PhaseSkipped { phase } => {
+            format!("⏭ {}: {}", pick(es, "Fase saltada, ahora", "Phase skipped, now"), phase_name(*phase, locale))
+        }
+        ResponsePayload::PhaseExtended { time_left } => {
+            format!("⏩ {} {}", pick(es, "Fase alargada, quedan", "Phase extended,"), remaining_text(*time_left, es))
+        }
+        ResponsePayload::PhaseShortened { time_left } => {
+            format!("⏪ {} {}", pick(es, "Fase acortada, quedan", "Phase shortened,"), remaining_text(*time_left, es))
+        }
+        ResponsePayload::PhaseRestarted { time_left } => {
+            format!("🔁 {} {}", pick(es, "Fase reiniciada, quedan", "Phase restarted,"), remaining_text(*time_left, es))
+        }
+        ResponsePayload::AwayResolved { seconds, kept } => format!(
+            "👋 {} {}",
+            format_clock(*seconds),
+            if *kept {
+                pick(es, "de ausencia conservados", "of away time kept")
+            } else {
+                pick(es, "de ausencia descartados", "of away time discarded")
+            }
+        ),
+        ResponsePayload::Continued => pick(es, "▶️ Fase confirmada", "▶️ Phase confirmed"),
+        ResponsePayload::Status(status) => {
+            let cfg = &status.config;
+            let state = state_name(cfg.state, es);
+            // Con secuencia propia se muestra el nombre del paso
+            let phase = match cfg.current_step() {
+                Some(step) if !cfg.sequence.is_empty() || cfg.mode == TimerMode::Flow => step.name,
+                _ => phase_name(cfg.current_phase, locale).to_string(),
+            };
+            // El flujo muestra el tiempo trabajado en lugar del que queda
+            let clock = if cfg.is_counting_up() {
+                format!("↑{}", format_clock(cfg.flow_elapsed))
+            } else {
+                format_clock(cfg.time_left)
+            };
+            let mut text = format!(
+                "{} · {} · {} {}/{} · {}",
+                phase,
+                clock,
+                pick(es, "ciclo", "cycle"),
+                cfg.current_cycle,
+                if cfg.repeat { "∞".to_string() } else { cfg.planned_cycles().to_string() },
+                state
+            );
+            if let Some(cause) = cfg.paused_by {
+                let reason = match cause {
+                    PauseCause::Suspend => pick(es, "por suspensión", "by suspend"),
+                    PauseCause::Lock => pick(es, "por bloqueo de pantalla", "by screen lock"),
+                    PauseCause::Idle => pick(es, "por inactividad", "by inactivity"),
+                };
+                text.push_str(&format!("\n⏸ {} {}", pick(es, "Pausado", "Paused"), reason));
+            }
+            if let Some(away) = &cfg.away {
+                let note = if away.returned {
+                    pick(es, "pendiente de resolveaway", "waiting for resolveaway")
+                } else {
+                    pick(es, "en curso", "ongoing")
+                };
+                text.push_str(&format!("\n💤 {} {} ({})", pick(es, "Ausencia:", "Away:"), format_clock(away.seconds), note));
+            }
+            if let Some(profile) = &cfg.active_profile {
+                text.push_str(&format!("\n{}: {}", pick(es, "Perfil", "Profile"), profile));
+            }
+            if let Some(task) = &status.task {
+                text.push_str(&format!("\n📝 {}: {}", pick(es, "Tarea", "Task"), task_line(task)));
+            }
+            if let Some(pending) = &cfg.pending_profile {
+                text.push_str(&format!("\n{}: {}", pick(es, "Siguiente perfil", "Next profile"), pending.name));
+            }
+            if let Some(urls) = &status.blocked_urls {
+                text.push_str(&format!("\n{}: {}", pick(es, "Bloqueados", "Blocked"), urls.join(", ")));
+            }
+            for timer in &status.timers {
+                text.push_str(&format!(
+                    "\n⏲ {} ({}) · {} · {}",
+                    timer.label,
+                    timer.id,
+                    format_clock(timer.time_left),
+                    state_name(timer.state, es)
+                ));
+            }
+            text
+        }
+        ResponsePayload::ConfigPath { path } => {
+            format!("🗂 {}: {}", pick(es, "Configuración en", "Config file at"), path)
+        }
+        ResponsePayload::ProgressReset => pick(es, "🔄 Progreso reiniciado", "🔄 Progress reset"),
+        ResponsePayload::ConfigReset => {
+            pick(es, "🔄 Configuración restablecida", "🔄 Config set to default")
+        }
+        ResponsePayload::SoundPlayed => pick(es, "🔊 Sonido de prueba", "🔊 Test sound played"),
+        ResponsePayload::Help(commands) => {
+            let mut text = pick(es, "🆘 Comandos disponibles:", "🆘 Available commands:");
+            for command in commands {
+                text.push_str(&format!("\n  {:<14} {}", command, describe_command(command, locale)));
+            }
+            text
+        }
+        ResponsePayload::Authenticated => pick(es, "🔑 Autenticado", "🔑 Authenticated"),
+        ResponsePayload::UrlBlocked { url } => {
+            format!("🔒 {} {}", url, pick(es, "añadida a la lista de bloqueo", "added to the blocklist"))
+        }
+        ResponsePayload::UrlUnblocked { url } => {
+            format!("🔓 {} {}", url, pick(es, "quitada de la lista de bloqueo", "removed from the blocklist"))
+        }
+        ResponsePayload::List(items) => items.join("\n"),
+        ResponsePayload::ConfigUpdated => pick(es, "✅ Configuración actualizada", "✅ Config updated"),
+        ResponsePayload::ConfigPatched => pick(es, "✅ Configuración modificada", "✅ Config patched"),
+        ResponsePayload::TaskAdded(task) => {
+            format!("📝 {} {}", pick(es, "Tarea creada:", "Task added:"), task_line(task))
+        }
+        ResponsePayload::TaskUpdated(task) => {
+            format!("📝 {} {}", pick(es, "Tarea actualizada:", "Task updated:"), task_line(task))
+        }
+        ResponsePayload::TaskDeleted { id } => {
+            format!("🗑️ {} #{}", pick(es, "Tarea borrada:", "Task deleted:"), id)
+        }
+        ResponsePayload::Tasks(tasks) => {
+            if tasks.is_empty() {
+                pick(es, "No hay tareas", "No tasks")
+            } else {
+                tasks.iter().map(task_line).collect::<Vec<_>>().join("\n")
+            }
+        }
+        ResponsePayload::TaskSet { task } => match task {
+            Some(task) => format!("📝 {} {}", pick(es, "Tarea activa:", "Active task:"), task_line(task)),
+            None => pick(es, "📝 Sin tarea activa", "📝 No active task"),
+        },
+        ResponsePayload::History(records) => {
+            if records.is_empty() {
+                pick(es, "El historial está vacío", "History is empty")
+            } else {
+                records.iter().map(|record| history_line(record, locale)).collect::<Vec<_>>().join("\n")
+            }
+        }
+        ResponsePayload::HistoryExported { content, .. } => content.clone(),
+        ResponsePayload::HistoryImported { added, skipped } => format!(
+            "📥 {} {} · {} {}",
+            added,
+            pick(es, "fases importadas", "phases imported"),
+            skipped,
+            pick(es, "ya estaban", "already present")
+        ),
+        ResponsePayload::Stats(stats) => stats_text(stats, es),
+        ResponsePayload::TimerCreated { id } => {
+            format!("⏲ {} {}", pick(es, "Temporizador creado:", "Timer created:"), id)
+        }
+        ResponsePayload::TimerRemoved { id } => {
+            format!("🗑️ {} {}", pick(es, "Temporizador eliminado:", "Timer removed:"), id)
+        }
+        ResponsePayload::ProfileSaved { name } => {
+            format!("💾 {} {}", pick(es, "Perfil guardado:", "Profile saved:"), name)
+        }
+        ResponsePayload::ProfileDeleted { name } => {
+            format!("🗑️ {} {}", pick(es, "Perfil borrado:", "Profile deleted:"), name)
+        }
+        ResponsePayload::Profiles(profiles) => {
+            if profiles.is_empty() {
+                pick(es, "No hay perfiles guardados", "No saved profiles")
+            } else {
+                profiles.keys().cloned().collect::<Vec<_>>().join("\n")
+            }
+        }
+        ResponsePayload::ProfileApplied { name } => {
+            format!("🎛 {} {}", pick(es, "Perfil aplicado:", "Profile applied:"), name)
+        }
+        ResponsePayload::ProfileScheduled { name } => format!(
+            "🎛 {} {} {}",
+            pick(es, "El perfil", "Profile"),
+            name,
+            pick(es, "se aplicará al terminar la fase", "will be applied when the phase ends")
+        ),
+        ResponsePayload::ShutdownRequested => {
+            pick(es, "🛑 Apagando el servidor", "🛑 Server shutting down")
+        }
+        ResponsePayload::ServerShuttingDown => {
+            pick(es, "🛑 El servidor se está apagando", "🛑 The server is shutting down")
+        }
+        ResponsePayload::Batch(results) => results
+            .iter()
+            .map(|result| render(result, locale))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        ResponsePayload::Error(error) => render_error(error, locale),
+    }
+}
+
+pub fn render_error(error: &ApiError, locale: Locale) -> String {
+    let es = locale == Locale::Es;
+    match error {
+        ApiError::InvalidCommand { reason } => {
+            format!("❌ {}: {}", pick(es, "Comando inválido", "Invalid command"), reason)
+        }
+        ApiError::ValidationFailed { field, reason } => {
+            format!("❌ {} `{}`: {}", pick(es, "Valor no válido en", "Invalid value for"), field, reason)
+        }
+        ApiError::Forbidden { reason } => {
+            format!("🔒 {}: {}", pick(es, "Acceso denegado", "Forbidden"), reason)
+        }
+        ApiError::NotFound { reason } => {
+            format!("❌ {}: {}", pick(es, "No encontrado", "Not found"), reason)
+        }
+        ApiError::NotRunning => pick(es, "⚠️ El temporizador no está en marcha", "⚠️ The timer is not running"),
+        ApiError::NotWaiting => {
+            pick(es, "⚠️ Ninguna fase espera confirmación", "⚠️ No phase is waiting for confirmation")
+        }
+        ApiError::AlreadyRunning => {
+            pick(es, "⚠️ El temporizador ya está en marcha", "⚠️ The timer is already running")
+        }
+        ApiError::Io { reason } => {
+            format!("❌ {}: {}", pick(es, "Error de entrada/salida", "I/O error"), reason)
+        }
+    }
+}
+
+fn describe_command(command: &str, locale: Locale) -> String {
+    let (es, en) = match command {
+        "start" => ("Inicia el temporizador", "Start the Pomodoro timer"),
+        "pause" => ("Pausa el temporizador", "Pause the timer"),
+        "resume" => ("Reanuda el temporizador", "Resume the timer"),
+        "skip" => ("Termina la fase actual y pasa a la siguiente", "End the current phase and move on"),
+        "extend" => ("Añade tiempo a la fase actual (seconds)", "Add time to the current phase (seconds)"),
+        "shorten" => ("Quita tiempo a la fase actual (seconds)", "Remove time from the current phase (seconds)"),
+        "continue" => ("Arranca la fase que espera confirmación", "Start the phase waiting for confirmation"),
+        "resolveaway" => ("Conserva o descarta el tiempo ausente (keep)", "Keep or discard away time (keep)"),
+        "restartphase" => ("Empieza de nuevo la fase actual", "Restart the current phase from full length"),
+        "status" => ("Muestra el estado actual", "Show current timer status"),
+        "myconfig" => ("Muestra dónde está la configuración", "Show config file location"),
+        "resetprogress" => ("Reinicia el progreso", "Reset progress"),
+        "resetconfig" => ("Restablece toda la configuración", "Reset entire config"),
+        "test" => ("Reproduce un sonido de prueba", "Play test sound"),
+        "auth" => ("Autentica la conexión (token)", "Authenticate this connection (token)"),
+        "block" => ("Bloquea una web (url)", "Block a website (url)"),
+        "unblock" => ("Desbloquea una web (url)", "Unblock a website (url)"),
+        "listblocked" => ("Lista las webs bloqueadas", "List blocked websites"),
+        "updateconfig" => ("Reemplaza la configuración (new_config)", "Replace the config (new_config)"),
+        "patchconfig" => ("Cambia solo los campos dados", "Change only the given settings"),
+        "saveprofile" => ("Guarda un perfil (name, profile)", "Create or replace a profile (name, profile)"),
+        "deleteprofile" => ("Borra un perfil (name)", "Delete a profile (name)"),
+        "listprofiles" => ("Lista los perfiles guardados", "List saved profiles"),
+        "useprofile" => ("Aplica un perfil (name, immediately)", "Apply a profile (name, immediately)"),
+        "addtask" => ("Crea una tarea (title, estimate, tags)", "Add a task (title, estimate, tags)"),
+        "updatetask" => ("Cambia una tarea (id, title, estimate, done, tags)", "Change a task (id, title, estimate, done, tags)"),
+        "deletetask" => ("Borra una tarea (id)", "Delete a task (id)"),
+        "listtasks" => ("Lista las tareas", "List tasks"),
+        "settask" => ("Elige la tarea activa (id)", "Set the active task (id)"),
+        "history" => ("Muestra el historial (from, to, filter)", "Show the history (from, to, filter)"),
+        "exporthistory" => ("Exporta el historial (format, from, to, filter)", "Export the history (format, from, to, filter)"),
+        "importhistory" => ("Importa fases exportadas (sessions)", "Import exported phases (sessions)"),
+        "stats" => ("Estadísticas del historial (from, to, filter)", "History statistics (from, to, filter)"),
+        "createtimer" => ("Crea un temporizador aparte (id, duration, label)", "Start a side timer (id, duration, label)"),
+        "removetimer" => ("Elimina un temporizador aparte (id)", "Remove a side timer (id)"),
+        "batch" => ("Aplica varios comandos a la vez (commands)", "Apply several commands atomically (commands)"),
+        "shutdown" => ("Apaga el servidor", "Stop the server cleanly"),
+        "help" => ("Muestra esta ayuda", "Show this help message"),
+        _ => ("", ""),
+    };
+    pick(locale == Locale::Es, es, en)
+}
+
+/// `#3 Escribir informe (2/4 🍅)`, con ✔ si está terminada.
+fn task_line(task: &Task) -> String {
+    let count = match task.estimate {
+        Some(estimate) => format!("{}/{}", task.pomodoros, estimate),
+        None => task.pomodoros.to_string(),
+    };
+    let done = if task.done { " ✔" } else { "" };
+    let tags: String = task.tags.iter().map(|tag| format!(" #{}", tag)).collect();
+    format!("#{} {} ({} 🍅){}{}", task.id, task.title, count, done, tags)
+}
+
+fn stats_text(stats: &Stats, es: bool) -> String {
+    let mut lines = vec![
+        format!(
+            "📊 {}: {} 🍅 · {}: {} 🍅",
+            pick(es, "Hoy", "Today"),
+            stats.today,
+            pick(es, "Semana", "Week"),
+            stats.this_week
+        ),
+        format!(
+            "⏱ {}: {} {} {} 🍅",
+            pick(es, "Foco", "Focus"),
+            duration_text(stats.focus_seconds),
+            pick(es, "en", "in"),
+            stats.pomodoros
+        ),
+        format!(
+            "✅ {}: {:.0}% · {}: {:.1}",
+            pick(es, "Completadas", "Completed"),
+            stats.completion_rate * 100.0,
+            pick(es, "interrupciones por fase", "interruptions per phase"),
+            stats.interruptions_per_phase
+        ),
+        format!(
+            "🔥 {}: {} ({}: {}) · {} {} 🍅/{}",
+            pick(es, "Racha", "Streak"),
+            stats.current_streak,
+            pick(es, "mejor", "best"),
+            stats.longest_streak,
+            pick(es, "objetivo", "goal"),
+            stats.daily_goal,
+            pick(es, "día", "day")
+        ),
+    ];
+    if !stats.tasks.is_empty() {
+        lines.push(format!("📝 {}:", pick(es, "Tareas", "Tasks")));
+        for task in &stats.tasks {
+            let title = task.title.as_ref().map(|title| format!(" {}", title)).unwrap_or_default();
+            lines.push(format!("   #{}{} · {} 🍅 · {}", task.id, title, task.pomodoros, duration_text(task.focus_seconds)));
+        }
+    }
+    if !stats.tags.is_empty() {
+        lines.push(format!("🏷 {}:", pick(es, "Etiquetas", "Tags")));
+        for tag in &stats.tags {
+            lines.push(format!("   #{} · {} 🍅 · {}", tag.tag, tag.pomodoros, duration_text(tag.focus_seconds)));
+        }
+    }
+    if !stats.best_hours.is_empty() {
+        let hours: Vec<String> = stats
+            .best_hours
+            .iter()
+            .map(|hour| format!("{:02}:00 ({})", hour.hour, duration_text(hour.focus_seconds)))
+            .collect();
+        lines.push(format!("🕘 {}: {}", pick(es, "Mejores horas", "Best hours"), hours.join(", ")));
+    }
+    lines.join("\n")
+}
+
+/// `1h 05m`, o solo minutos por debajo de una hora.
+fn duration_text(seconds: u32) -> String {
+    let minutes = seconds / 60;
+    if minutes >= 60 {
+        format!("{}h {:02}m", minutes / 60, minutes % 60)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+/// `2026-10-19 09:25 🔨 Trabajo ✅ terminada 25:00/25:00 #3`
+fn history_line(record: &HistoryRecord, locale: Locale) -> String {
+    let es = locale == Locale::Es;
+    let when = Local
+        .timestamp_opt(record.at, 0)
+        .single()
+        .map_or_else(|| record.at.to_string(), |at| at.format("%Y-%m-%d %H:%M").to_string());
+    let what = match &record.event {
+        HistoryEvent::Started { .. } => pick(es, "▶️ empieza", "▶️ started"),
+        HistoryEvent::Paused => pick(es, "⏸ pausa", "⏸ paused"),
+        HistoryEvent::Resumed => pick(es, "▶️ sigue", "▶️ resumed"),
+        HistoryEvent::Adjusted(kind) => match kind {
+            SessionEventKind::Extended { seconds } => format!("⏩ +{}s", seconds),
+            SessionEventKind::Shortened { seconds } => format!("⏪ -{}s", seconds),
+            SessionEventKind::PhaseRestarted => pick(es, "🔁 reiniciada", "🔁 restarted"),
+            SessionEventKind::Suspended { seconds, .. } => format!("🌙 {} {}s", pick(es, "suspendido", "suspended"), seconds),
+            SessionEventKind::Away { seconds, .. } => format!("👋 {} {}s", pick(es, "ausente", "away"), seconds),
+            SessionEventKind::Skipped => pick(es, "⏭ saltada", "⏭ skipped"),
+            SessionEventKind::FlowStopped { worked } => format!("🌊 {}", format_clock(*worked)),
+        },
+        HistoryEvent::Ended { planned, actual, outcome } => {
+            let label = match outcome {
+                PhaseOutcome::Completed => pick(es, "✅ terminada", "✅ completed"),
+                PhaseOutcome::Skipped => pick(es, "⏭ saltada", "⏭ skipped"),
+                PhaseOutcome::Interrupted => pick(es, "⛔ cortada", "⛔ interrupted"),
+            };
+            if *planned == 0 {
+                format!("{} {}", label, format_clock(*actual))
+            } else {
+                format!("{} {}/{}", label, format_clock(*actual), format_clock(*planned))
+            }
+        }
+    };
+    let task = record.task.map(|id| format!(" #{}", id)).unwrap_or_default();
+    format!("{} {} {}{}", when, phase_name(record.phase, locale), what, task)
+}
+
+fn state_name(state: TimerState, es: bool) -> &'static str {
+    match (state, es) {
+        (TimerState::Idle, true) => "sin iniciar",
+        (TimerState::Idle, false) => "not started",
+        (TimerState::Running, true) => "en marcha",
+        (TimerState::Running, false) => "running",
+        (TimerState::Paused, true) => "en pausa",
+        (TimerState::Paused, false) => "paused",
+        (TimerState::WaitingForConfirmation, true) => "esperando confirmación (continue)",
+        (TimerState::WaitingForConfirmation, false) => "waiting for confirmation (continue)",
+        (TimerState::Finished, true) => "terminado",
+        (TimerState::Finished, false) => "finished",
+    }
+}
+
+fn phase_name(phase: Phase, locale: Locale) -> &'static str {
+    match (phase, locale) {
+        (Phase::Work, Locale::Es) => "🔨 Trabajo",
+        (Phase::Work, Locale::En) => "🔨 Work",
+        (Phase::ShortBreak, Locale::Es) => "☕ Pausa corta",
+        (Phase::ShortBreak, Locale::En) => "☕ Short break",
+        (Phase::LongBreak, Locale::Es) => "🛌 Pausa larga",
+        (Phase::LongBreak, Locale::En) => "🛌 Long break",
+        (Phase::Idle, Locale::Es) => "🕒 En espera",
+        (Phase::Idle, Locale::En) => "🕒 Idle",
+    }
+}
+
+fn format_clock(seconds: u32) -> String {
+    format!("{:02}:{:02}", seconds / 60, seconds % 60)
+}
+
+fn remaining_text(seconds: u32, es: bool) -> String {
+    if es {
+        format_clock(seconds)
+    } else {
+        format!("{} left", format_clock(seconds))
+    }
+}
+
+fn pick(es: bool, spanish: &str, english: &str) -> String {
+    if es { spanish } else { english }.to_string()
+}
@@ -1,113 +1,354 @@
-use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    Arc,
-};
-use tokio::{sync::Mutex as AsyncMutex, task::JoinHandle, time::{sleep, Duration}};
+use std::sync::Arc;
+use tokio::{sync::Mutex as AsyncMutex, task::JoinHandle, time::Duration};
 use std::io::{stdout, Write};
 
 use crate::{
-    config::{Phase, PomodoroStateConfig},
-    utils::{play_sound, show_notification},
+    clock::{Clock, SystemClock},
+    config::{Phase, PhaseStep, PomodoroStateConfig, TimerMode, TimerState, MAX_DURATION_SECS},
+    history::{self, HistoryEvent, PhaseOutcome, SessionEvent, SessionEventKind},
+    profiles::apply_profile,
+    tasks::{record_pomodoro, TasksLock},
+    utils::{DesktopNotifier, Notifier, SiteBlocker},
 };
 
+/// Transición no válida desde el estado actual del motor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransitionError {
+    AlreadyRunning,
+    NotRunning,
+    /// La fase de flujo cuenta hacia arriba: no tiene tiempo que ajustar
+    CountingUp,
+    /// `continue` sin ninguna fase esperando confirmación
+    NotWaiting,
+}
+
+/// Dueño de la única tarea del temporizador. El estado (`TimerState`) vive en
+/// la configuración compartida; aquí solo se decide qué transiciones valen y
+/// se arranca o aborta la tarea.
 pub struct PomodoroHandle {
-    pub is_running: Arc<AtomicBool>,
-    pub handle: Option<JoinHandle<()>>,
+    handle: Option<JoinHandle<()>>,
+    effects: Effects,
+}
+
+/// Lo que la tarea del temporizador toca fuera de la configuración.
+#[derive(Clone)]
+struct Effects {
+    blocker: Arc<SiteBlocker>,
+    clock: Arc<dyn Clock>,
+    notifier: Arc<dyn Notifier>,
+    tasks: TasksLock,
+}
+
+impl Default for PomodoroHandle {
+    fn default() -> Self {
+        Self::new(Arc::new(SiteBlocker::new(None)))
+    }
 }
 
 impl PomodoroHandle {
-    pub fn new() -> Self {
+    pub fn new(blocker: Arc<SiteBlocker>) -> Self {
         Self {
-            is_running: Arc::new(AtomicBool::new(false)),
             handle: None,
+            effects: Effects {
+                blocker,
+                clock: Arc::new(SystemClock),
+                notifier: Arc::new(DesktopNotifier),
+                tasks: TasksLock::default(),
+            },
         }
     }
 
-    pub fn start(&mut self, config: Arc<AsyncMutex<PomodoroStateConfig>>) {
-        let is_running = self.is_running.clone();
-        is_running.store(true, Ordering::SeqCst);
+    /// Usa `clock` para las esperas entre ticks.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.effects.clock = clock;
+        self
+    }
 
-        let handle: JoinHandle<()> = tokio::spawn(async move {
-            loop {
-                {
-                    let cfg = config.lock().await;
-                    if !is_running.load(Ordering::SeqCst) || cfg.current_phase == Phase::Idle {
-                        break;
-                    }
-                }
+    /// Usa `notifier` para los avisos y sonidos de cada fase.
+    pub fn with_notifier(mut self, notifier: Arc<dyn Notifier>) -> Self {
+        self.effects.notifier = notifier;
+        self
+    }
+
+    /// Comparte el cerrojo de `tasks.json` con quien más lo use.
+    pub fn with_tasks_lock(mut self, tasks: TasksLock) -> Self {
+        self.effects.tasks = tasks;
+        self
+    }
+
+    /// `Idle` → `Running` desde la fase guardada, `Finished` → `Running` con un
+    /// ciclo nuevo y `Paused` o `WaitingForConfirmation` → `Running` como un
+    /// `resume` o un `continue`.
+    pub async fn start(&mut self, config: Arc<AsyncMutex<PomodoroStateConfig>>) -> Result<(), TransitionError> {
+        {
+            let mut cfg = config.lock().await;
+            match cfg.state {
+                TimerState::Running => return Err(TransitionError::AlreadyRunning),
+                TimerState::Finished => cfg.reset_progress(),
+                TimerState::Paused => history::record(&cfg, &*self.effects.clock, HistoryEvent::Resumed).await,
+                TimerState::Idle | TimerState::WaitingForConfirmation => {}
+            }
+            cfg.set_state(TimerState::Running);
+            let _ = cfg.save_config().await;
+        }
+
+        self.spawn_timer(config);
+        Ok(())
+    }
+
+    pub async fn pause(&mut self, config: Arc<AsyncMutex<PomodoroStateConfig>>) -> Result<(), TransitionError> {
+        {
+            let mut cfg = config.lock().await;
+            if cfg.state != TimerState::Running {
+                return Err(TransitionError::NotRunning);
+            }
+            cfg.set_state(TimerState::Paused);
+            history::record(&cfg, &*self.effects.clock, HistoryEvent::Paused).await;
+            let _ = cfg.save_config().await;
+        }
 
-                run_phase(Arc::clone(&config), is_running.clone()).await;
+        self.abort_timer();
+        println!("⏸ Pomodoro pausado");
+        Ok(())
+    }
 
-                let cfg = config.lock().await;
-                if cfg.current_phase == Phase::Idle || !cfg.is_running {
-                    break;
+    pub async fn resume(&mut self, config: Arc<AsyncMutex<PomodoroStateConfig>>) -> Result<(), TransitionError> {
+        {
+            let mut cfg = config.lock().await;
+            match cfg.state {
+                TimerState::Paused => {}
+                TimerState::Running => return Err(TransitionError::AlreadyRunning),
+                TimerState::Idle | TimerState::WaitingForConfirmation | TimerState::Finished => {
+                    return Err(TransitionError::NotRunning)
                 }
             }
+            cfg.set_state(TimerState::Running);
+            history::record(&cfg, &*self.effects.clock, HistoryEvent::Resumed).await;
+            let _ = cfg.save_config().await;
+        }
 
-            println!("🛑 Ciclo de Pomodoro detenido.");
-            // ❌ NO vuelvas a llamar run_phase aquí
-        });
+        println!("▶️ Reanudando Pomodoro...");
+        self.spawn_timer(config);
+        Ok(())
+    }
 
-        self.handle = Some(handle);
+    /// Arranca la fase que espera confirmación.
+    pub async fn continue_phase(&mut self, config: Arc<AsyncMutex<PomodoroStateConfig>>) -> Result<(), TransitionError> {
+        {
+            let mut cfg = config.lock().await;
+            if cfg.state != TimerState::WaitingForConfirmation {
+                return Err(TransitionError::NotWaiting);
+            }
+            cfg.set_state(TimerState::Running);
+            let _ = cfg.save_config().await;
+        }
+
+        println!("▶️ Fase confirmada");
+        self.spawn_timer(config);
+        Ok(())
     }
 
-    pub async fn pause(&mut self, config: Arc<AsyncMutex<PomodoroStateConfig>>) {
-        let mut cfg = config.lock().await;
-        cfg.is_running = false;
-        let _ = PomodoroStateConfig::save_config(&cfg).await;
-        self.is_running.store(false, Ordering::SeqCst);
-        println!("⏸ Pomodoro pausado");
+    /// Deja el temporizador en pausa (si corría) y la tarea terminada, con el
+    /// estado guardado. Se usa al apagar el servidor.
+    pub async fn stop(&mut self, config: Arc<AsyncMutex<PomodoroStateConfig>>) {
+        let _ = self.pause(Arc::clone(&config)).await;
+        self.abort_timer();
+        let _ = config.lock().await.save_config().await;
     }
 
-    pub async fn resume(&mut self, config: Arc<AsyncMutex<PomodoroStateConfig>>) {
-        if !self.is_running.load(Ordering::SeqCst) {
-            println!("▶️ Reanudando Pomodoro...");
-            self.start(config);
+    /// Termina la fase actual y pasa a la siguiente. En marcha, la nueva fase
+    /// empieza enseguida (con su aviso) salvo que pida confirmación; en pausa
+    /// queda lista para `resume`. Saltar la última fase termina el ciclo.
+    pub async fn skip(&mut self, config: Arc<AsyncMutex<PomodoroStateConfig>>) -> Result<Phase, TransitionError> {
+        let (next, running, waiting, task) = {
+            let mut cfg = config.lock().await;
+            let was_waiting = cfg.state == TimerState::WaitingForConfirmation;
+            if !matches!(cfg.state, TimerState::Running | TimerState::Paused) && !was_waiting {
+                return Err(TransitionError::NotRunning);
+            }
+            // Abortar con el bloqueo tomado: la tarea vieja ya no descuenta nada
+            self.abort_timer();
+
+            // El trabajo en flujo solo termina así: cuenta como completado
+            let task = if cfg.is_counting_up() { cfg.active_task } else { None };
+            let kind = if cfg.is_counting_up() {
+                SessionEventKind::FlowStopped { worked: cfg.flow_elapsed }
+            } else {
+                SessionEventKind::Skipped
+            };
+            let outcome = if cfg.is_counting_up() { PhaseOutcome::Completed } else { PhaseOutcome::Skipped };
+            let event = SessionEvent::new(self.effects.clock.now().timestamp(), cfg.current_phase, kind);
+            cfg.push_event(event);
+            history::end_phase(&mut cfg, &*self.effects.clock, outcome).await;
+            let next = advance(&mut cfg, &self.effects.blocker).await;
+            cfg.time_left = cfg.current_duration();
+            let running = cfg.state == TimerState::Running;
+            let waiting = next != Phase::Idle && (was_waiting || (running && !cfg.auto_starts()));
+            if next == Phase::Idle {
+                cfg.set_state(TimerState::Finished);
+            } else if waiting {
+                cfg.set_state(TimerState::WaitingForConfirmation);
+            }
+            let _ = cfg.save_config().await;
+            (next, running, waiting, task)
+        };
+
+        println!("⏭ Fase saltada");
+        if let Some(id) = task {
+            record_pomodoro(&self.effects.tasks, id).await;
         }
+        if next == Phase::Idle {
+            let cfg = config.lock().await;
+            announce_finished(&*self.effects.notifier, cfg.sounds.finished.as_deref(), cfg.timer_label.as_deref());
+        } else if waiting {
+            self.abort_timer();
+            self.handle = Some(tokio::spawn(wait_for_confirmation(config, self.effects.clone())));
+        } else if running {
+            self.spawn_timer(config);
+        }
+        Ok(next)
+    }
+
+    /// Añade tiempo a la fase actual, sin pasar de `MAX_DURATION_SECS`.
+    pub async fn extend(&self, config: Arc<AsyncMutex<PomodoroStateConfig>>, seconds: u32) -> Result<u32, TransitionError> {
+        adjust_time_left(config, &*self.effects.clock, SessionEventKind::Extended { seconds }, |cfg| {
+            cfg.remaining().saturating_add(seconds).min(MAX_DURATION_SECS)
+        })
+        .await
+    }
+
+    /// Quita tiempo a la fase actual. Siempre queda al menos un segundo, así
+    /// que la fase termina con su aviso normal; para acabarla ya está `skip`.
+    pub async fn shorten(&self, config: Arc<AsyncMutex<PomodoroStateConfig>>, seconds: u32) -> Result<u32, TransitionError> {
+        adjust_time_left(config, &*self.effects.clock, SessionEventKind::Shortened { seconds }, |cfg| {
+            cfg.remaining().saturating_sub(seconds).max(1)
+        })
+        .await
+    }
+
+    /// Vuelve a poner la fase actual con su duración completa.
+    pub async fn restart_phase(&self, config: Arc<AsyncMutex<PomodoroStateConfig>>) -> Result<u32, TransitionError> {
+        adjust_time_left(config, &*self.effects.clock, SessionEventKind::PhaseRestarted, |cfg| cfg.current_duration())
+        .await
     }
 
     pub async fn reset_progress(&mut self, config: Arc<AsyncMutex<PomodoroStateConfig>>) {
+        self.abort_timer();
         let mut cfg = config.lock().await;
+        history::end_phase(&mut cfg, &*self.effects.clock, PhaseOutcome::Interrupted).await;
         if let Err(e) = cfg.reset_mut().await {
             eprintln!("❌ Error al hacer reset_mut: {}", e);
         }
     }
 
     pub async fn reset(&mut self, config: Arc<AsyncMutex<PomodoroStateConfig>>) {
+        self.abort_timer();
         let mut cfg = config.lock().await;
+        history::end_phase(&mut cfg, &*self.effects.clock, PhaseOutcome::Interrupted).await;
         if let Err(e) = cfg.reset().await {
             eprintln!("❌ Error al hacer reset_mut: {}", e);
         }
     }
+
+    /// Siempre hay como mucho una tarea: la anterior se aborta antes de lanzar
+    /// otra, así dos `start` nunca descuentan el tiempo dos veces.
+    fn spawn_timer(&mut self, config: Arc<AsyncMutex<PomodoroStateConfig>>) {
+        self.abort_timer();
+        self.handle = Some(tokio::spawn(run_cycle(config, self.effects.clone())));
+    }
+
+    fn abort_timer(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            handle.abort();
+        }
+    }
+}
+
+/// La tarea del temporizador lee `time_left` en cada tick, así que basta con
+/// cambiarlo: no hace falta reiniciarla.
+async fn adjust_time_left(
+    config: Arc<AsyncMutex<PomodoroStateConfig>>,
+    clock: &dyn Clock,
+    event: SessionEventKind,
+    new_time_left: impl FnOnce(&PomodoroStateConfig) -> u32,
+) -> Result<u32, TransitionError> {
+    let mut cfg = config.lock().await;
+    if !matches!(cfg.state, TimerState::Running | TimerState::Paused) {
+        return Err(TransitionError::NotRunning);
+    }
+    if cfg.is_counting_up() {
+        return Err(TransitionError::CountingUp);
+    }
+    cfg.time_left = new_time_left(&cfg);
+    let phase = cfg.current_phase;
+    cfg.push_event(SessionEvent::new(clock.now().timestamp(), phase, event.clone()));
+    history::record(&cfg, clock, HistoryEvent::Adjusted(event)).await;
+    let _ = cfg.save_config().await;
+    Ok(cfg.time_left)
+}
+
+async fn run_cycle(config: Arc<AsyncMutex<PomodoroStateConfig>>, effects: Effects) {
+    loop {
+        let advanced = run_phase(Arc::clone(&config), &effects).await;
+
+        let mut cfg = config.lock().await;
+        if cfg.state != TimerState::Running {
+            break;
+        }
+        if cfg.current_phase == Phase::Idle {
+            cfg.set_state(TimerState::Finished);
+            let _ = cfg.save_config().await;
+            let sound = cfg.sounds.finished.clone();
+            let label = cfg.timer_label.clone();
+            drop(cfg);
+            announce_finished(&*effects.notifier, sound.as_deref(), label.as_deref());
+            break;
+        }
+        if advanced && !cfg.auto_starts() {
+            cfg.time_left = cfg.current_duration();
+            cfg.set_state(TimerState::WaitingForConfirmation);
+            let _ = cfg.save_config().await;
+            drop(cfg);
+            wait_for_confirmation(Arc::clone(&config), effects).await;
+            return;
+        }
+    }
+
+    println!("🛑 Ciclo de Pomodoro detenido.");
 }
 
 async fn run_timer(
     config: Arc<AsyncMutex<PomodoroStateConfig>>,
     duration_secs: u32,
-    is_running: Arc<AtomicBool>,
+    clock: &dyn Clock,
 ) {
     {
         let mut cfg = config.lock().await;
         cfg.time_left = if cfg.time_left > 0 { cfg.time_left } else { duration_secs };
-        cfg.is_running = true;
     }
 
     println!("⏱ Timer iniciado");
 
     loop {
         {
-            let mut cfg = config.lock().await;
-            if !cfg.is_running || !is_running.load(Ordering::SeqCst) || cfg.time_left == 0 {
+            let cfg = config.lock().await;
+            if cfg.state != TimerState::Running || cfg.time_left == 0 {
                 break;
             }
-
-            cfg.time_left -= 1;
-            let _ = PomodoroStateConfig::save_config(&cfg).await;
-            print!("\r⏳ Tiempo restante: {} segundos", cfg.time_left);
-            stdout().flush().unwrap();
         }
 
-        sleep(Duration::from_secs(1)).await;
+        // Primero se espera y luego se descuenta: cada segundo cuenta entero
+        clock.sleep(Duration::from_secs(1)).await;
+
+        let mut cfg = config.lock().await;
+        if cfg.state != TimerState::Running {
+            break;
+        }
+        cfg.time_left = cfg.time_left.saturating_sub(1);
+        cfg.phase_elapsed += 1;
+        let _ = PomodoroStateConfig::save_config(&cfg).await;
+        print!("\r⏳ Tiempo restante: {} segundos", cfg.time_left);
+        stdout().flush().unwrap();
     }
 
     {
@@ -120,64 +361,221 @@ async fn run_timer(
     }
 }
 
-async fn run_phase(config: Arc<AsyncMutex<PomodoroStateConfig>>, is_running: Arc<AtomicBool>) {
-    let phase = {
-        config.lock().await.current_phase
+/// Cuenta hacia arriba mientras el temporizador esté en marcha.
+async fn run_flow(config: Arc<AsyncMutex<PomodoroStateConfig>>, clock: &dyn Clock) {
+    println!("⏱ Flujo iniciado");
+
+    loop {
+        clock.sleep(Duration::from_secs(1)).await;
+
+        let mut cfg = config.lock().await;
+        if cfg.state != TimerState::Running || !cfg.is_counting_up() {
+            break;
+        }
+        cfg.flow_elapsed += 1;
+        cfg.phase_elapsed += 1;
+        let _ = PomodoroStateConfig::save_config(&cfg).await;
+        print!("\r🌊 Tiempo en flujo: {} segundos", cfg.flow_elapsed);
+        stdout().flush().unwrap();
+    }
+}
+
+/// Avisa de que la fase espera `continue` y, con `nag_minutes`, lo recuerda
+/// hasta que se confirme. `continue` aborta esta tarea al lanzar la fase.
+async fn wait_for_confirmation(config: Arc<AsyncMutex<PomodoroStateConfig>>, effects: Effects) {
+    let (step, nag_minutes) = {
+        let cfg = config.lock().await;
+        (cfg.current_step(), cfg.auto_start.nag_minutes)
+    };
+    let Some(step) = step else {
+        return;
     };
 
-    match phase {
-        Phase::Work => {
-            println!("🔨 Trabajando...");
-            show_notification("🔨 Trabajando...", "Pomodoro en curso");
-            play_sound("kuru-ring-herta-made-with-Voicemod.mp3");
-            let duration = config.lock().await.work_duration;
-            run_timer(Arc::clone(&config), duration, is_running).await;
-        }
-        Phase::ShortBreak => {
-            println!("☕ Pausa corta...");
-            show_notification("☕ Pausa corta...", "Tómate un descanso corto");
-            play_sound("kuru-kuru-herta-made-with-Voicemod.mp3");
-            let duration = config.lock().await.break_duration;
-            run_timer(Arc::clone(&config), duration, is_running).await;
-        }
-        Phase::LongBreak => {
-            println!("🛌 Pausa larga...");
-            show_notification("🛌 Pausa larga...", "Tómate un descanso largo");
-            play_sound("kuru-kuru-herta-made-with-Voicemod.mp3");
-            let duration = config.lock().await.long_break_duration;
-            run_timer(Arc::clone(&config), duration, is_running).await;
-        }
-        Phase::Idle => {
-            show_notification("🕒 Pomodoro finalizado", "Pomodoro finalizado o en espera");
-            play_sound("aqua-crying-green-screen-with-crying-sounds-made-with-Voicemod.mp3");
-            println!("🕒 Pomodoro finalizado o en espera.");
+    let message = format!("{} empezará con `continue`", step.name);
+    println!("⏳ Esperando confirmación: {}", step.name);
+    effects.notifier.notify("⏳ Esperando confirmación", &message);
+
+    let Some(minutes) = nag_minutes else {
+        return;
+    };
+    loop {
+        effects.clock.sleep(Duration::from_secs(u64::from(minutes) * 60)).await;
+        if config.lock().await.state != TimerState::WaitingForConfirmation {
             return;
         }
+        effects.notifier.notify("⏰ Sigues en espera", &message);
+        effects.notifier.play_sound(default_sound(step.kind));
+    }
+}
+
+/// Devuelve si la fase terminó y se pasó a la siguiente.
+async fn run_phase(config: Arc<AsyncMutex<PomodoroStateConfig>>, effects: &Effects) -> bool {
+    let blocker = &*effects.blocker;
+    let step = {
+        let mut cfg = config.lock().await;
+        let Some(step) = cfg.current_step() else {
+            return false;
+        };
+        if cfg.phase_session.is_none() {
+            history::begin_phase(&mut cfg, &*effects.clock).await;
+        }
+        step
+    };
+
+    println!("{}", step.name);
+    effects.notifier.notify(&step.name, step.notification.as_deref().unwrap_or(default_message(step.kind)));
+    effects.notifier.play_sound(step.sound.as_deref().unwrap_or(default_sound(step.kind)));
+    if step.block_sites && !blocker.is_blocking() {
+        blocker.block();
     }
+    // El flujo solo termina con `skip`, que ya pasa a la pausa
+    if config.lock().await.is_counting_up() {
+        run_flow(config, &*effects.clock).await;
+        return false;
+    }
+    run_timer(Arc::clone(&config), step.duration, &*effects.clock).await;
 
     let mut cfg = config.lock().await;
-    if cfg.time_left == 0 {
-        cfg.current_phase = next_phase(&mut cfg);
-        cfg.time_left = 0;
-        PomodoroStateConfig::save_config(&cfg).await;
+    if cfg.time_left != 0 {
+        return false;
+    }
+    let task = if step.kind == Phase::Work { cfg.active_task } else { None };
+    history::end_phase(&mut cfg, &*effects.clock, PhaseOutcome::Completed).await;
+    advance(&mut cfg, blocker).await;
+    cfg.time_left = 0;
+    let _ = PomodoroStateConfig::save_config(&cfg).await;
+    drop(cfg);
+    if let Some(id) = task {
+        record_pomodoro(&effects.tasks, id).await;
+    }
+    true
+}
+
+/// Paso de una fase a la siguiente: aquí se aplica el perfil pendiente y
+/// se levanta el bloqueo si la fase nueva no lo pide.
+async fn advance(cfg: &mut PomodoroStateConfig, blocker: &SiteBlocker) -> Phase {
+    let from = cfg.current_step();
+    cfg.current_phase = next_phase(cfg);
+    if let Some(pending) = cfg.pending_profile.take() {
+        if let Err(e) = apply_profile(cfg, blocker, &pending.name, &pending.profile).await {
+            eprintln!("❌ No se pudo aplicar el perfil {}: {:?}", pending.name, e);
+        }
+    }
+    if let Some(from) = from {
+        leave_step(blocker, &from, cfg.current_step().as_ref());
+    }
+    cfg.current_phase
+}
+
+/// Levanta el bloqueo al pasar de un paso que bloquea a uno que no (o al
+/// terminar el ciclo). Los bloqueos hechos a mano fuera de un paso, o
+/// retenidos por la agenda, no se tocan.
+fn leave_step(blocker: &SiteBlocker, from: &PhaseStep, to: Option<&PhaseStep>) {
+    if from.block_sites && !to.is_some_and(|step| step.block_sites) && !blocker.is_held() {
+        blocker.unblock();
+    }
+}
+
+fn default_message(kind: Phase) -> &'static str {
+    match kind {
+        Phase::Work => "Pomodoro en curso",
+        Phase::ShortBreak => "Tómate un descanso corto",
+        Phase::LongBreak | Phase::Idle => "Tómate un descanso largo",
     }
 }
 
+fn default_sound(kind: Phase) -> &'static str {
+    match kind {
+        Phase::Work => "kuru-ring-herta-made-with-Voicemod.mp3",
+        _ => "kuru-kuru-herta-made-with-Voicemod.mp3",
+    }
+}
+
+fn announce_finished(notifier: &dyn Notifier, sound: Option<&str>, timer_label: Option<&str>) {
+    if let Some(label) = timer_label {
+        notifier.notify(&format!("⏲ {}", label), "Temporizador terminado");
+        notifier.play_sound(sound.unwrap_or("kuru-kuru-herta-made-with-Voicemod.mp3"));
+        println!("⏲ Temporizador {} terminado.", label);
+        return;
+    }
+    notifier.notify("🕒 Pomodoro finalizado", "Pomodoro finalizado o en espera");
+    notifier.play_sound(sound.unwrap_or("aqua-crying-green-screen-with-crying-sounds-made-with-Voicemod.mp3"));
+    println!("🕒 Pomodoro finalizado o en espera.");
+}
+
+/// Fase que sigue a la actual (ver el modelo de ciclos en
+/// `PomodoroStateConfig`). Cada bloque de trabajo suma un ciclo al
+/// terminar; el temporizador acaba tras la pausa del último, o tras el
+/// propio bloque si no hay `final_long_break`.
 fn next_phase(cfg: &mut PomodoroStateConfig) -> Phase {
+    if cfg.mode == TimerMode::Flow {
+        return next_flow_phase(cfg);
+    }
+    if !cfg.sequence.is_empty() {
+        return next_sequence_phase(cfg);
+    }
+
     match cfg.current_phase {
         Phase::Work => {
             cfg.current_cycle += 1;
-            if cfg.current_cycle == cfg.cycles {
-                return Phase::Idle;
-            }
-            if cfg.current_cycle % cfg.long_break_interval == 0 {
+            if cycles_done(cfg) {
+                if cfg.final_long_break {
+                    Phase::LongBreak
+                } else {
+                    Phase::Idle
+                }
+            } else if cfg.current_cycle.is_multiple_of(cfg.long_break_interval) {
                 Phase::LongBreak
             } else {
                 Phase::ShortBreak
             }
         }
         Phase::ShortBreak | Phase::LongBreak => {
-            if cfg.current_cycle >= cfg.cycles {
+            if cycles_done(cfg) {
+                Phase::Idle
+            } else {
+                Phase::Work
+            }
+        }
+        Phase::Idle => Phase::Idle,
+    }
+}
+
+/// Con `repeat` nunca se acaba.
+fn cycles_done(cfg: &PomodoroStateConfig) -> bool {
+    !cfg.repeat && cfg.current_cycle >= cfg.planned_cycles()
+}
+
+/// Avanza un paso en la secuencia personalizada. Cada vuelta completa es un
+/// ciclo; tras `cycles` vueltas, el temporizador termina.
+fn next_sequence_phase(cfg: &mut PomodoroStateConfig) -> Phase {
+    if cfg.current_phase == Phase::Idle {
+        return Phase::Idle;
+    }
+    cfg.current_step += 1;
+    if cfg.current_step >= cfg.sequence.len() {
+        cfg.current_step = 0;
+        cfg.current_cycle += 1;
+        if cycles_done(cfg) {
+            return Phase::Idle;
+        }
+    }
+    cfg.sequence[cfg.current_step].kind
+}
+
+/// Trabajo y pausa se alternan; la pausa dura `break_percent` del tiempo
+/// trabajado (al menos un segundo). Cada pausa cierra un ciclo.
+fn next_flow_phase(cfg: &mut PomodoroStateConfig) -> Phase {
+    match cfg.current_phase {
+        Phase::Work => {
+            let worked = u64::from(cfg.flow_elapsed);
+            cfg.flow_break = (worked * u64::from(cfg.flow.break_percent) / 100).max(1) as u32;
+            cfg.flow_elapsed = 0;
+            cfg.current_cycle += 1;
+            Phase::ShortBreak
+        }
+        Phase::ShortBreak | Phase::LongBreak => {
+            if cycles_done(cfg) {
                 Phase::Idle
             } else {
                 Phase::Work
@@ -186,3 +584,305 @@ fn next_phase(cfg: &mut PomodoroStateConfig) -> Phase {
         Phase::Idle => Phase::Idle,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FakeClock;
+    use crate::utils::RecordingNotifier;
+    use tokio::time::sleep;
+
+    fn classic(cycles: u32, long_break_interval: u32) -> PomodoroStateConfig {
+        let mut cfg = PomodoroStateConfig::new();
+        cfg.in_memory = true;
+        cfg.work_duration = 60;
+        cfg.break_duration = 10;
+        cfg.long_break_duration = 20;
+        cfg.cycles = cycles;
+        cfg.long_break_interval = long_break_interval;
+        cfg.reset_progress();
+        cfg
+    }
+
+    fn phases(cfg: &mut PomodoroStateConfig) -> Vec<Phase> {
+        let mut phases = vec![cfg.current_phase];
+        while cfg.current_phase != Phase::Idle && phases.len() < 100 {
+            cfg.current_phase = next_phase(cfg);
+            phases.push(cfg.current_phase);
+        }
+        phases
+    }
+
+    fn engine(cfg: PomodoroStateConfig) -> (PomodoroHandle, Arc<AsyncMutex<PomodoroStateConfig>>, Arc<RecordingNotifier>) {
+        let notifier = Arc::new(RecordingNotifier::default());
+        let handle = PomodoroHandle::new(Arc::new(SiteBlocker::simulated()))
+            .with_clock(Arc::new(FakeClock::new(chrono::Local::now())))
+            .with_notifier(notifier.clone());
+        (handle, Arc::new(AsyncMutex::new(cfg)), notifier)
+    }
+
+    #[test]
+    fn classic_cycle_alternates_work_and_breaks() {
+        let mut cfg = classic(4, 2);
+        use Phase::*;
+        assert_eq!(phases(&mut cfg), vec![Work, ShortBreak, Work, LongBreak, Work, ShortBreak, Work, LongBreak, Idle]);
+        assert_eq!(cfg.current_cycle, 4);
+    }
+
+    #[test]
+    fn session_cycles_override_the_setting() {
+        let mut cfg = classic(4, 2);
+        cfg.session_cycles = Some(1);
+        use Phase::*;
+        assert_eq!(phases(&mut cfg), vec![Work, LongBreak, Idle]);
+        cfg.reset_progress();
+        assert_eq!((cfg.session_cycles, cfg.planned_cycles()), (None, 4));
+    }
+
+    #[test]
+    fn long_break_follows_every_interval() {
+        let mut cfg = classic(6, 3);
+        let breaks: Vec<Phase> = phases(&mut cfg)
+            .into_iter()
+            .filter(|phase| matches!(phase, Phase::ShortBreak | Phase::LongBreak))
+            .collect();
+        use Phase::*;
+        assert_eq!(breaks, vec![ShortBreak, ShortBreak, LongBreak, ShortBreak, ShortBreak, LongBreak]);
+    }
+
+    #[test]
+    fn sequence_repeats_steps_cycles_times() {
+        let mut cfg = classic(2, 2);
+        let step = |kind| PhaseStep {
+            name: format!("{:?}", kind),
+            kind,
+            duration: 30,
+            block_sites: false,
+            sound: None,
+            notification: None,
+            auto_start: None,
+        };
+        cfg.sequence = vec![step(Phase::Work), step(Phase::Work), step(Phase::LongBreak)];
+        cfg.reset_progress();
+        use Phase::*;
+        assert_eq!(phases(&mut cfg), vec![Work, Work, LongBreak, Work, Work, LongBreak, Idle]);
+    }
+
+    #[test]
+    fn flow_break_is_a_share_of_the_work() {
+        let mut cfg = classic(1, 1);
+        cfg.mode = TimerMode::Flow;
+        cfg.flow.break_percent = 20;
+        cfg.flow_elapsed = 50 * 60;
+        assert_eq!(next_phase(&mut cfg), Phase::ShortBreak);
+        assert_eq!(cfg.flow_break, 10 * 60);
+        assert_eq!(cfg.flow_elapsed, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn pause_keeps_time_left_until_resume() {
+        let (mut handle, config, _) = engine(classic(4, 2));
+        handle.start(config.clone()).await.unwrap();
+        sleep(Duration::from_millis(10_500)).await;
+
+        handle.pause(config.clone()).await.unwrap();
+        let paused_at = config.lock().await.time_left;
+        assert!((46..60).contains(&paused_at), "time_left = {}", paused_at);
+        sleep(Duration::from_secs(30)).await;
+        assert_eq!(config.lock().await.time_left, paused_at);
+        assert_eq!(handle.pause(config.clone()).await, Err(TransitionError::NotRunning));
+
+        handle.resume(config.clone()).await.unwrap();
+        sleep(Duration::from_millis(5_500)).await;
+        let cfg = config.lock().await;
+        assert_eq!(cfg.state, TimerState::Running);
+        assert!((paused_at - 6..paused_at).contains(&cfg.time_left), "time_left = {}", cfg.time_left);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn phase_session_counts_only_running_time() {
+        let (mut handle, config, _) = engine(classic(4, 2));
+        handle.start(config.clone()).await.unwrap();
+        sleep(Duration::from_millis(10_500)).await;
+        handle.pause(config.clone()).await.unwrap();
+        sleep(Duration::from_secs(30)).await;
+        {
+            let cfg = config.lock().await;
+            assert!(cfg.phase_session.as_deref().is_some_and(|id| id.ends_with("-Work")));
+            assert_eq!(cfg.phase_elapsed, 60 - cfg.time_left);
+        }
+
+        handle.skip(config.clone()).await.unwrap();
+        assert_eq!(config.lock().await.phase_session, None);
+        handle.resume(config.clone()).await.unwrap();
+        sleep(Duration::from_millis(500)).await;
+        assert!(config.lock().await.phase_session.as_deref().is_some_and(|id| id.ends_with("-ShortBreak")));
+        handle.reset_progress(config.clone()).await;
+        assert_eq!(config.lock().await.phase_session, None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn session_events_use_the_engine_clock() {
+        let start = chrono::Local::now() - chrono::Duration::days(3);
+        let config = Arc::new(AsyncMutex::new(classic(4, 2)));
+        let mut handle = PomodoroHandle::new(Arc::new(SiteBlocker::simulated()))
+            .with_clock(Arc::new(FakeClock::new(start)))
+            .with_notifier(Arc::new(RecordingNotifier::default()));
+        handle.start(config.clone()).await.unwrap();
+        sleep(Duration::from_millis(2_500)).await;
+        handle.extend(config.clone(), 30).await.unwrap();
+        let events = config.lock().await.session_events.clone();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].at, start.timestamp() + 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn start_twice_is_rejected() {
+        let (mut handle, config, _) = engine(classic(4, 2));
+        handle.start(config.clone()).await.unwrap();
+        assert_eq!(handle.start(config.clone()).await, Err(TransitionError::AlreadyRunning));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn whole_cycle_runs_to_finished() {
+        let (mut handle, config, notifier) = engine(classic(2, 2));
+        handle.start(config.clone()).await.unwrap();
+        sleep(Duration::from_secs(60 * 60)).await;
+
+        let cfg = config.lock().await;
+        assert_eq!(cfg.state, TimerState::Finished);
+        assert_eq!(cfg.current_phase, Phase::Idle);
+        assert_eq!(cfg.current_cycle, 2);
+        let notifications = notifier.notifications.lock().unwrap();
+        assert_eq!(notifications.last().map(String::as_str), Some("🕒 Pomodoro finalizado"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn recovered_config_resumes_where_it_stopped() {
+        let mut saved = classic(4, 2);
+        saved.set_state(TimerState::Running);
+        saved.time_left = 42;
+        let mut recovered = PomodoroStateConfig::from_saved(&serde_json::to_string(&saved).unwrap()).unwrap();
+        recovered.in_memory = true;
+        assert_eq!(recovered.state, TimerState::Paused);
+
+        let (mut handle, config, _) = engine(recovered);
+        handle.resume(config.clone()).await.unwrap();
+        sleep(Duration::from_millis(2_500)).await;
+        let time_left = config.lock().await.time_left;
+        assert!((38..42).contains(&time_left), "time_left = {}", time_left);
+    }
+
+    mod properties {
+        use super::*;
+        use proptest::prelude::*;
+
+        proptest! {
+            #[test]
+            fn classic_cycle_follows_the_model(cycles in 1u32..30, interval in 1u32..10, final_long_break: bool) {
+                let mut cfg = classic(cycles, interval);
+                cfg.final_long_break = final_long_break;
+                let phases = phases(&mut cfg);
+
+                prop_assert_eq!(phases.last(), Some(&Phase::Idle));
+                prop_assert_eq!(phases.iter().filter(|phase| **phase == Phase::Work).count() as u32, cycles);
+                prop_assert_eq!(cfg.current_cycle, cycles);
+                let mut block = 0;
+                for pair in phases.windows(2) {
+                    if pair[0] != Phase::Work {
+                        prop_assert!(pair[1] == Phase::Work || pair[1] == Phase::Idle);
+                        continue;
+                    }
+                    block += 1;
+                    let expected = if block == cycles {
+                        if final_long_break { Phase::LongBreak } else { Phase::Idle }
+                    } else if block % interval == 0 {
+                        Phase::LongBreak
+                    } else {
+                        Phase::ShortBreak
+                    };
+                    prop_assert_eq!(pair[1], expected, "after block {}", block);
+                }
+            }
+
+            #[test]
+            fn repeat_never_finishes(cycles in 1u32..10, interval in 1u32..5, steps in 1usize..300) {
+                let mut cfg = classic(cycles, interval);
+                cfg.repeat = true;
+                let mut works = 0;
+                for _ in 0..steps {
+                    if cfg.current_phase == Phase::Work {
+                        works += 1;
+                    }
+                    cfg.current_phase = next_phase(&mut cfg);
+                    prop_assert_ne!(cfg.current_phase, Phase::Idle);
+                }
+                prop_assert_eq!(cfg.current_cycle, works);
+            }
+
+            #[test]
+            fn sequence_runs_cycles_full_passes(cycles in 1u32..10, len in 1usize..6) {
+                let mut cfg = classic(cycles, 1);
+                cfg.sequence = (0..len)
+                    .map(|i| PhaseStep {
+                        name: format!("paso {}", i),
+                        kind: if i % 2 == 0 { Phase::Work } else { Phase::ShortBreak },
+                        duration: 30,
+                        block_sites: false,
+                        sound: None,
+                        notification: None,
+                        auto_start: None,
+                    })
+                    .collect();
+                cfg.reset_progress();
+                prop_assert_eq!(phases(&mut cfg).len(), cycles as usize * len + 1);
+            }
+
+            #[test]
+            fn state_is_the_only_source_of_truth(ops in prop::collection::vec(0u8..5, 1..25)) {
+                let runtime = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .start_paused(true)
+                    .build()
+                    .unwrap();
+                runtime.block_on(async {
+                    let (mut handle, config, _) = engine(classic(3, 2));
+                    for op in ops {
+                        let before = config.lock().await.state;
+                        let result = match op {
+                            0 => handle.start(config.clone()).await,
+                            1 => handle.pause(config.clone()).await,
+                            2 => handle.resume(config.clone()).await,
+                            3 => handle.skip(config.clone()).await.map(|_| ()),
+                            _ => {
+                                let left = config.lock().await.time_left;
+                                sleep(Duration::from_secs(7)).await;
+                                let cfg = config.lock().await;
+                                if before != TimerState::Running {
+                                    prop_assert_eq!(cfg.time_left, left);
+                                }
+                                Ok(())
+                            }
+                        };
+                        let cfg = config.lock().await;
+                        match op {
+                            0 => prop_assert_eq!(result.is_ok(), before != TimerState::Running),
+                            1 => prop_assert_eq!(result.is_ok(), before == TimerState::Running),
+                            2 => prop_assert_eq!(result.is_ok(), before == TimerState::Paused),
+                            _ => {}
+                        }
+                        if result.is_ok() && op <= 2 {
+                            let expected = if op == 1 { TimerState::Paused } else { TimerState::Running };
+                            prop_assert_eq!(cfg.state, expected);
+                        }
+                        prop_assert_eq!(cfg.is_running(), cfg.state == TimerState::Running);
+                        prop_assert_eq!(cfg.state == TimerState::Finished, cfg.current_phase == Phase::Idle);
+                        prop_assert!(cfg.time_left <= cfg.current_duration());
+                    }
+                    Ok(())
+                })?;
+            }
+        }
+    }
+
+}
@@ -0,0 +1,131 @@
+use std::{collections::BTreeMap, path::Path};
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::blocked_sites::{is_hostname, save_urls, validate_url};
+use crate::config::{data_file, ConfigPatch, IdleSourceSettings, PomodoroStateConfig, ValidationError};
+use crate::protocol::ApiError;
+use crate::utils::SiteBlocker;
+
+/// Ajustes con nombre que se aplican de una vez con `useprofile`. Solo se
+/// cambian los campos presentes en `settings`; `blocked_sites`, si está,
+/// sustituye la lista de bloqueo.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    #[serde(default)]
+    pub settings: ConfigPatch,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blocked_sites: Option<Vec<String>>,
+}
+
+impl Profile {
+    /// Los perfiles llegan de los clientes y no pueden fijar `idle.source`:
+    /// se guarda sin fuente y al aplicarlo se conserva la del archivo.
+    pub fn strip_idle_source(&mut self) {
+        if let Some(idle) = &mut self.settings.idle {
+            idle.source = IdleSourceSettings::Disabled;
+        }
+    }
+}
+
+/// Perfil esperando al final de la fase actual para aplicarse.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PendingProfile {
+    pub name: String,
+    pub profile: Profile,
+}
+
+pub const MAX_PROFILE_NAME_LEN: usize = 64;
+
+pub fn profiles_path() -> String {
+    data_file("profiles.json")
+}
+
+pub async fn read_profiles() -> BTreeMap<String, Profile> {
+    if !Path::new(&profiles_path()).exists() {
+        return BTreeMap::new();
+    }
+
+    let content = fs::read_to_string(&profiles_path()).await.unwrap_or_default();
+    match serde_json::from_str::<BTreeMap<String, Profile>>(&content) {
+        Ok(mut profiles) => {
+            // Un archivo editado a mano no puede colar nada raro en el hosts
+            for (name, profile) in profiles.iter_mut() {
+                if let Some(sites) = &mut profile.blocked_sites {
+                    let before = sites.len();
+                    sites.retain(|site| is_hostname(site));
+                    if sites.len() != before {
+                        eprintln!("⚠️  Perfil {}: webs no válidas descartadas", name);
+                    }
+                }
+            }
+            profiles
+        }
+        Err(e) => {
+            eprintln!("❌ Error al leer los perfiles: {}", e);
+            BTreeMap::new()
+        }
+    }
+}
+
+pub async fn save_profiles(profiles: &BTreeMap<String, Profile>) -> std::io::Result<()> {
+    let json = serde_json::to_string_pretty(profiles).map_err(std::io::Error::other)?;
+    let tmp_path = format!("{}.tmp", profiles_path());
+    fs::write(&tmp_path, json).await?;
+    fs::rename(&tmp_path, &profiles_path()).await
+}
+
+pub fn validate_profile_name(name: &str) -> Result<(), ValidationError> {
+    if name.trim().is_empty() || name.len() > MAX_PROFILE_NAME_LEN {
+        return Err(ValidationError {
+            field: "name",
+            message: format!("must be between 1 and {} characters", MAX_PROFILE_NAME_LEN),
+        });
+    }
+    Ok(())
+}
+
+/// Resultado de aplicar `profile` sobre `cfg`, ya validado.
+pub fn preview_profile(cfg: &PomodoroStateConfig, profile: &Profile) -> Result<PomodoroStateConfig, ValidationError> {
+    for site in profile.blocked_sites.iter().flatten() {
+        validate_url(site)?;
+    }
+    let mut patched = cfg.clone();
+    patched.apply_patch(&profile.settings);
+    patched.idle.source = cfg.idle.source.clone();
+    patched.validate_change(cfg)?;
+    Ok(patched)
+}
+
+/// Aplica `profile` sobre `cfg` si el resultado es válido. Si trae lista de
+/// bloqueo, la sustituye; con webs ya bloqueadas, se rehace el bloqueo con la
+/// lista nueva. Si la lista no se puede guardar, `cfg` queda como estaba.
+pub async fn apply_profile(
+    cfg: &mut PomodoroStateConfig,
+    blocker: &SiteBlocker,
+    name: &str,
+    profile: &Profile,
+) -> Result<(), ApiError> {
+    let mut patched = preview_profile(cfg, profile)?;
+    patched.active_profile = Some(name.to_string());
+
+    if let Some(sites) = &profile.blocked_sites {
+        let was_blocking = blocker.is_blocking();
+        if was_blocking {
+            blocker.unblock();
+        }
+        let saved = save_urls(&sites.iter().cloned().collect()).await;
+        if was_blocking {
+            blocker.block();
+        }
+        if let Err(e) = saved {
+            eprintln!("❌ Error al guardar la lista de bloqueo del perfil {}: {}", name, e);
+            return Err(ApiError::io(e));
+        }
+    }
+    *cfg = patched;
+    println!("🎛 Perfil aplicado: {}", name);
+    Ok(())
+}
@@ -0,0 +1,367 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{ConfigPatch, Phase, PomodoroStateConfig, TimerState, ValidationError};
+use crate::export::{CompletedSession, ExportFormat};
+use crate::history::{HistoryFilter, HistoryRecord};
+use crate::pomodoro::TransitionError;
+use crate::profiles::Profile;
+use crate::stats::Stats;
+use crate::tasks::Task;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "lowercase")]
+pub enum ClientCommand {
+    Start,
+    Pause,
+    Resume,
+    Skip,
+    Extend { seconds: u32 },
+    Shorten { seconds: u32 },
+    RestartPhase,
+    Continue,
+    /// Conserva (`keep`) o descarta el tiempo de la última ausencia
+    ResolveAway { keep: bool },
+    Status,
+    MyConfig,
+    ResetProgress,
+    ResetConfig,
+    Test,
+    Help,
+    Auth { token: String },
+    Block { url: String },
+    Unblock { url: String },
+    UpdateConfig { new_config: Box<PomodoroStateConfig> },
+    PatchConfig(ConfigPatch),
+    ListBlocked,
+    SaveProfile { name: String, profile: Profile },
+    DeleteProfile { name: String },
+    ListProfiles,
+    /// Sin `immediately`, con el temporizador en marcha o en pausa, el perfil
+    /// espera al final de la fase actual.
+    UseProfile {
+        name: String,
+        #[serde(default)]
+        immediately: bool,
+    },
+    AddTask {
+        title: String,
+        #[serde(default)]
+        estimate: Option<u32>,
+        #[serde(default)]
+        tags: Vec<String>,
+    },
+    /// Cambia solo los campos presentes
+    UpdateTask {
+        id: u32,
+        #[serde(default)]
+        title: Option<String>,
+        #[serde(default)]
+        estimate: Option<u32>,
+        #[serde(default)]
+        done: Option<bool>,
+        #[serde(default)]
+        tags: Option<Vec<String>>,
+    },
+    DeleteTask { id: u32 },
+    ListTasks,
+    /// Sin `id`, ninguna tarea queda activa
+    SetTask {
+        #[serde(default)]
+        id: Option<u32>,
+    },
+    /// Líneas del historial entre los días `from` y `to` (`AAAA-MM-DD`,
+    /// ambos incluidos) que pasan `filter`
+    History {
+        #[serde(default)]
+        from: Option<String>,
+        #[serde(default)]
+        to: Option<String>,
+        #[serde(default)]
+        filter: HistoryFilter,
+    },
+    /// Fases cerradas del historial en CSV, JSON o iCalendar; `from`, `to`
+    /// y `filter` como en `History`
+    ExportHistory {
+        format: ExportFormat,
+        #[serde(default)]
+        from: Option<String>,
+        #[serde(default)]
+        to: Option<String>,
+        #[serde(default)]
+        filter: HistoryFilter,
+    },
+    /// Añade al historial las fases que aún no estén (por `id`)
+    ImportHistory { sessions: Vec<CompletedSession> },
+    /// Estadísticas del historial; `from`, `to` y `filter` como en `History`
+    Stats {
+        #[serde(default)]
+        from: Option<String>,
+        #[serde(default)]
+        to: Option<String>,
+        #[serde(default)]
+        filter: HistoryFilter,
+    },
+    /// Crea y arranca un temporizador secundario de una sola fase
+    CreateTimer {
+        id: String,
+        #[serde(default)]
+        label: Option<String>,
+        duration: u32,
+    },
+    RemoveTimer { id: String },
+    Shutdown,
+    Batch { commands: Vec<ClientCommand> },
+}
+
+/// Identificador del temporizador principal en el campo `timer`.
+pub const MAIN_TIMER_ID: &str = "main";
+
+/// Un comando tal como llega por WebSocket o por el socket Unix. `timer`
+/// elige un temporizador secundario; sin él (o con `"main"`), el principal.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TimerRequest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timer: Option<String>,
+    #[serde(flatten)]
+    pub command: ClientCommand,
+}
+
+impl ClientCommand {
+    /// Comandos que solo tocan la configuración o la lista de bloqueo y que,
+    /// por tanto, pueden aplicarse juntos dentro de un `Batch`.
+    pub fn is_batchable(&self) -> bool {
+        matches!(
+            self,
+            ClientCommand::Status
+                | ClientCommand::ListBlocked
+                | ClientCommand::Block { .. }
+                | ClientCommand::Unblock { .. }
+                | ClientCommand::UpdateConfig { .. }
+                | ClientCommand::PatchConfig(_)
+        )
+    }
+
+    /// Comandos que no cambian nada y, por tanto, no necesitan que se
+    /// difunda el estado en cuanto terminan.
+    pub fn is_read_only(&self) -> bool {
+        matches!(
+            self,
+            ClientCommand::Status
+                | ClientCommand::MyConfig
+                | ClientCommand::Test
+                | ClientCommand::Help
+                | ClientCommand::Auth { .. }
+                | ClientCommand::ListBlocked
+                | ClientCommand::ListProfiles
+                | ClientCommand::ListTasks
+                | ClientCommand::History { .. }
+                | ClientCommand::ExportHistory { .. }
+                | ClientCommand::Stats { .. }
+        )
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Deserialize)]
+pub struct StatusWithOptionalUrls {
+    #[serde(flatten)]
+    pub config: PomodoroStateConfig,
+
+    /// Igual a `state == Running`; se mantiene para clientes antiguos
+    #[serde(default)]
+    pub is_running: bool,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blocked_urls: Option<Vec<String>>,
+
+    /// Tarea activa
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task: Option<Task>,
+
+    /// Temporizadores secundarios, solo en el estado del principal
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub timers: Vec<TimerSummary>,
+}
+
+impl StatusWithOptionalUrls {
+    pub fn new(config: PomodoroStateConfig, blocked_urls: Option<Vec<String>>) -> Self {
+        Self {
+            is_running: config.is_running(),
+            config,
+            blocked_urls,
+            task: None,
+            timers: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Deserialize)]
+pub struct TimerSummary {
+    pub id: String,
+    pub label: String,
+    pub phase: Phase,
+    pub state: TimerState,
+    pub time_left: u32,
+}
+
+
+/// Nombres de los comandos tal como se escriben en el JSON, en el orden en
+/// que los lista `help`.
+pub const COMMAND_NAMES: &[&str] = &[
+    "start",
+    "pause",
+    "resume",
+    "skip",
+    "extend",
+    "shorten",
+    "restartphase",
+    "continue",
+    "resolveaway",
+    "status",
+    "myconfig",
+    "resetprogress",
+    "resetconfig",
+    "test",
+    "auth",
+    "block",
+    "unblock",
+    "listblocked",
+    "updateconfig",
+    "patchconfig",
+    "saveprofile",
+    "deleteprofile",
+    "listprofiles",
+    "useprofile",
+    "addtask",
+    "updatetask",
+    "deletetask",
+    "listtasks",
+    "settask",
+    "history",
+    "exporthistory",
+    "importhistory",
+    "stats",
+    "createtimer",
+    "removetimer",
+    "batch",
+    "shutdown",
+    "help",
+];
+
+/// Errores con un código estable para que los clientes puedan decidir qué
+/// hacer. `reason` es un detalle técnico en inglés; el texto para el usuario
+/// lo genera cada frontend (ver `messages`).
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(tag = "code")]
+pub enum ApiError {
+    InvalidCommand { reason: String },
+    ValidationFailed { field: String, reason: String },
+    Forbidden { reason: String },
+    NotFound { reason: String },
+    NotRunning,
+    AlreadyRunning,
+    NotWaiting,
+    Io { reason: String },
+}
+
+impl ApiError {
+    pub fn invalid(reason: impl Into<String>) -> Self {
+        ApiError::InvalidCommand { reason: reason.into() }
+    }
+
+    pub fn forbidden(reason: impl Into<String>) -> Self {
+        ApiError::Forbidden { reason: reason.into() }
+    }
+
+    pub fn not_found(reason: impl Into<String>) -> Self {
+        ApiError::NotFound { reason: reason.into() }
+    }
+
+    pub fn io(reason: impl std::fmt::Display) -> Self {
+        ApiError::Io { reason: reason.to_string() }
+    }
+}
+
+impl From<TransitionError> for ApiError {
+    fn from(error: TransitionError) -> Self {
+        match error {
+            TransitionError::AlreadyRunning => ApiError::AlreadyRunning,
+            TransitionError::NotRunning => ApiError::NotRunning,
+            TransitionError::NotWaiting => ApiError::NotWaiting,
+            TransitionError::CountingUp => ApiError::invalid("the flow phase counts up; use skip to end it"),
+        }
+    }
+}
+
+impl From<ValidationError> for ApiError {
+    fn from(error: ValidationError) -> Self {
+        ApiError::ValidationFailed {
+            field: error.field.to_string(),
+            reason: error.message,
+        }
+    }
+}
+
+/// Una respuesta por comando, sin texto para humanos: los frontends
+/// muestran lo que quieran a partir del tipo y sus datos.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data")]
+pub enum ResponsePayload {
+    Started,
+    Paused,
+    Resumed,
+    /// Fase en la que queda el temporizador tras el salto
+    PhaseSkipped { phase: Phase },
+    PhaseExtended { time_left: u32 },
+    PhaseShortened { time_left: u32 },
+    PhaseRestarted { time_left: u32 },
+    Continued,
+    AwayResolved { seconds: u32, kept: bool },
+    Status(Box<StatusWithOptionalUrls>),
+    ConfigPath { path: String },
+    ProgressReset,
+    ConfigReset,
+    SoundPlayed,
+    Help(Vec<String>),
+    Authenticated,
+    UrlBlocked { url: String },
+    UrlUnblocked { url: String },
+    List(Vec<String>),
+    ConfigUpdated,
+    ConfigPatched,
+    TaskAdded(Task),
+    TaskUpdated(Task),
+    TaskDeleted { id: u32 },
+    Tasks(Vec<Task>),
+    /// Tarea activa tras `settask`
+    TaskSet { task: Option<Task> },
+    History(Vec<HistoryRecord>),
+    HistoryExported { format: ExportFormat, content: String },
+    HistoryImported { added: u32, skipped: u32 },
+    Stats(Box<Stats>),
+    TimerCreated { id: String },
+    TimerRemoved { id: String },
+    ProfileSaved { name: String },
+    ProfileDeleted { name: String },
+    Profiles(BTreeMap<String, Profile>),
+    ProfileApplied { name: String },
+    /// El perfil se aplicará al terminar la fase actual
+    ProfileScheduled { name: String },
+    ShutdownRequested,
+    ServerShuttingDown,
+    Batch(Vec<ResponsePayload>),
+    Error(ApiError),
+}
+
+impl From<ApiError> for ResponsePayload {
+    fn from(error: ApiError) -> Self {
+        ResponsePayload::Error(error)
+    }
+}
+
+impl From<ValidationError> for ResponsePayload {
+    fn from(error: ValidationError) -> Self {
+        ResponsePayload::Error(error.into())
+    }
+}
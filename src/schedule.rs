@@ -0,0 +1,151 @@
+use chrono::{Datelike, NaiveDateTime, NaiveTime};
+
+use crate::config::{parse_clock_time, ScheduleSettings, ScheduledSession, Weekday};
+
+/// Qué pide la agenda en un momento dado.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScheduleAction {
+    /// Arrancar una sesión nueva, con `cycles` ciclos si la sesión lo indica
+    StartSession { cycles: Option<u32> },
+    /// Parar el temporizador y levantar los bloqueos
+    EndOfDay,
+}
+
+/// Recuerda la última consulta para disparar cada hora de la agenda una sola
+/// vez, al cruzarla. Las horas que pasaron antes de arrancar el servidor no
+/// se disparan.
+#[derive(Debug, Default)]
+pub struct Scheduler {
+    last_check: Option<NaiveDateTime>,
+    /// El bloqueo actual lo puso la agenda
+    pub blocking: bool,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Devuelve la última hora de la agenda cruzada desde la consulta
+    /// anterior. Si el equipo estuvo suspendido y se cruzaron varias, solo
+    /// cuenta la más reciente.
+    pub fn poll(&mut self, settings: &ScheduleSettings, now: NaiveDateTime) -> Option<ScheduleAction> {
+        let last = self.last_check.replace(now)?;
+        if now <= last {
+            return None;
+        }
+
+        let mut due: Option<(NaiveDateTime, ScheduleAction)> = None;
+        let mut consider = |at: NaiveDateTime, action: ScheduleAction| {
+            if last < at && at <= now && due.is_none_or(|(latest, _)| at >= latest) {
+                due = Some((at, action));
+            }
+        };
+        let mut date = last.date();
+        while date <= now.date() {
+            let day = Weekday::from(date.weekday());
+            for session in settings.sessions.iter().filter(|session| session.days.contains(&day)) {
+                if let Some(start) = parse_clock_time(&session.start) {
+                    consider(date.and_time(start), ScheduleAction::StartSession { cycles: session.cycles });
+                }
+            }
+            if let Some(end) = settings.end_of_day.as_deref().and_then(parse_clock_time) {
+                consider(date.and_time(end), ScheduleAction::EndOfDay);
+            }
+            let Some(next) = date.succ_opt() else {
+                break;
+            };
+            date = next;
+        }
+        due.map(|(_, action)| action)
+    }
+}
+
+/// Indica si `now` cae dentro de alguna ventana de la agenda: desde el
+/// `start` de una sesión hasta su `end` o, si no tiene, hasta `end_of_day`.
+pub fn in_window(settings: &ScheduleSettings, now: NaiveDateTime) -> bool {
+    let day = Weekday::from(now.weekday());
+    let end_of_day = settings.end_of_day.as_deref().and_then(parse_clock_time);
+    settings
+        .sessions
+        .iter()
+        .filter(|session| session.days.contains(&day))
+        .filter_map(|session| window(session, end_of_day))
+        .any(|(start, end)| start <= now.time() && now.time() < end)
+}
+
+fn window(session: &ScheduledSession, end_of_day: Option<NaiveTime>) -> Option<(NaiveTime, NaiveTime)> {
+    let start = parse_clock_time(&session.start)?;
+    let end = match session.end.as_deref() {
+        Some(end) => parse_clock_time(end)?,
+        None => end_of_day?,
+    };
+    (start < end).then_some((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    /// 2026-10-19 es lunes
+    fn monday(hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 10, 19).unwrap().and_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    fn weekdays() -> ScheduleSettings {
+        let days = vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri];
+        ScheduleSettings {
+            sessions: vec![
+                ScheduledSession { days: days.clone(), start: "09:00".into(), cycles: Some(4), end: Some("12:00".into()) },
+                ScheduledSession { days, start: "14:00".into(), cycles: None, end: None },
+            ],
+            end_of_day: Some("18:00".into()),
+            block_sites: true,
+        }
+    }
+
+    #[test]
+    fn fires_each_time_once_when_crossed() {
+        let settings = weekdays();
+        let mut scheduler = Scheduler::new();
+        assert_eq!(scheduler.poll(&settings, monday(8, 59)), None);
+        assert_eq!(scheduler.poll(&settings, monday(9, 0)), Some(ScheduleAction::StartSession { cycles: Some(4) }));
+        assert_eq!(scheduler.poll(&settings, monday(9, 1)), None);
+        assert_eq!(scheduler.poll(&settings, monday(14, 0)), Some(ScheduleAction::StartSession { cycles: None }));
+        assert_eq!(scheduler.poll(&settings, monday(18, 30)), Some(ScheduleAction::EndOfDay));
+    }
+
+    #[test]
+    fn times_before_the_first_poll_do_not_fire() {
+        let mut scheduler = Scheduler::new();
+        assert_eq!(scheduler.poll(&weekdays(), monday(9, 30)), None);
+    }
+
+    #[test]
+    fn only_the_latest_crossed_time_counts() {
+        let settings = weekdays();
+        let mut scheduler = Scheduler::new();
+        scheduler.poll(&settings, monday(8, 0));
+        assert_eq!(scheduler.poll(&settings, monday(19, 0)), Some(ScheduleAction::EndOfDay));
+    }
+
+    #[test]
+    fn weekend_is_skipped() {
+        let settings = weekdays();
+        let saturday = |hour| NaiveDate::from_ymd_opt(2026, 10, 24).unwrap().and_hms_opt(hour, 0, 0).unwrap();
+        let mut scheduler = Scheduler::new();
+        scheduler.poll(&settings, saturday(8));
+        assert_eq!(scheduler.poll(&settings, saturday(10)), None);
+        assert!(!in_window(&settings, saturday(10)));
+    }
+
+    #[test]
+    fn windows_end_at_end_or_end_of_day() {
+        let settings = weekdays();
+        assert!(in_window(&settings, monday(11, 59)));
+        assert!(!in_window(&settings, monday(12, 0)));
+        assert!(in_window(&settings, monday(17, 0)));
+        assert!(!in_window(&settings, monday(18, 0)));
+    }
+}
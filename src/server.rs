@@ -1,258 +1,333 @@
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 
 use futures_util::stream::SplitSink;
-use serde::{Deserialize, Serialize};
 use tokio::net::{TcpListener, TcpStream};
-use tokio_tungstenite::tungstenite::{client, Message};
-use tokio_tungstenite::{accept_async, WebSocketStream};
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::StatusCode;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{accept_hdr_async, WebSocketStream};
 use futures_util::{StreamExt, SinkExt};
 
-use crate::blocked_sites::{add_url, list_urls, remove_url};
-use crate::config::{self, config_path, PomodoroStateConfig};
-use crate::pomodoro::PomodoroHandle;
-use crate::utils::play_sound;
+use crate::auth::{is_origin_allowed, load_or_create_token, read_allowed_origins, token_from_request, tokens_match};
+use crate::http::start_http_server;
+use crate::protocol::{ApiError, ClientCommand, ResponsePayload, TimerRequest};
+use crate::service::PomodoroService;
+use crate::unix_socket::{bind_unix_socket, start_unix_server};
+use tokio::signal::unix::{signal, SignalKind};
 use tokio::task;
-use tokio::sync::Mutex as AsyncMutex;
-// use std::collections::HashSet;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{broadcast, mpsc, Mutex as AsyncMutex, Notify};
+use tokio::time::{timeout, Duration, Instant};
 
-type Client = Arc<AsyncMutex<SplitSink<WebSocketStream<TcpStream>, Message>>>;
-type Clients = Arc<AsyncMutex<HashMap<String, Client>>>;
+type WsSink = SplitSink<WebSocketStream<TcpStream>, Message>;
+type Clients = Arc<AsyncMutex<HashMap<usize, Client>>>;
 
-#[derive(Debug, Serialize, Deserialize)]
-struct StatusWithUrls {
-    #[serde(flatten)]
-    config: PomodoroStateConfig,
-    blocked_urls: Vec<String>,
-}
+static NEXT_CLIENT_ID: AtomicUsize = AtomicUsize::new(1);
 
-#[derive(Debug, Serialize, Clone, Deserialize)]
-pub struct StatusWithOptionalUrls {
-    #[serde(flatten)]
-    pub config: PomodoroStateConfig,
-    
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub blocked_urls: Option<Vec<String>>,
+/// Lado del difusor de un cliente: su cola de salida acotada. La tarea
+/// `client_writer` la vacía en el socket, así un cliente lento no frena al resto.
+struct Client {
+    queue: mpsc::Sender<Message>,
+    /// Última vez que la cola aceptó un estado
+    last_delivered: Instant,
+    kick: Arc<Notify>,
 }
 
-
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(tag = "type", content = "data")]
-pub enum ResponsePayload {
-    Message(String),
-    Status(StatusWithOptionalUrls),
-    Error(String),
-    Help(String),
-    List(Vec<String>),
-}
-
-
-
-pub async fn start_server(config: Arc<AsyncMutex<PomodoroStateConfig>>) -> Result<(), Box<dyn std::error::Error>> {
-    let pomodoro_handle = Arc::new(AsyncMutex::new(PomodoroHandle::new()));
+pub async fn start_server(service: PomodoroService) -> Result<(), Box<dyn std::error::Error>> {
     let clients: Clients = Arc::new(AsyncMutex::new(HashMap::new()));
+    let token = Arc::new(load_or_create_token()?);
+    // 🧦 Antes que nada: si otro servidor ya tiene el socket, no se arranca
+    let unix_listener = bind_unix_socket()?;
+    // Mismos mensajes que recibe cada cliente WebSocket, para los suscriptores SSE
+    let (events, _) = broadcast::channel::<String>(16);
 
-
+    // 🛑 Ctrl+C y SIGTERM piden el mismo apagado que el comando `shutdown`
+    {
+        let service = service.clone();
+        tokio::spawn(async move {
+            match wait_for_signal().await {
+                Ok(()) => println!("\n🛑 Señal recibida, apagando servidor..."),
+                Err(e) => eprintln!("❌ Error al escuchar señales: {}", e),
+            }
+            service.request_shutdown();
+        });
+    }
 
         // 🔁 Lanzar task que emite actualizaciones cada segundo
         let clients_clone = Arc::clone(&clients);
-        let config_clone = Arc::clone(&config);
+        let service_clone = service.clone();
+        let events_clone = events.clone();
+        let status_changed = service.status_changed();
         tokio::spawn(async move {
             loop {
-                // let config_json = {
-                //     serde_json::to_string(&*config).unwrap()
-                // };
-                let config = config_clone.lock().await;
-                let blocked_urls = match list_urls().await {
-                    Ok(urls) => urls,
-                    Err(e) => {
-                        eprintln!("❌ Error al listar URLs: {}", e);
-                        vec![]
-                    }
-                };
+                let status_with_optional_urls = service_clone.status(true).await;
 
-                let status_with_optional_urls = StatusWithOptionalUrls {
-                    config: config.clone(),
-                    blocked_urls: Some(blocked_urls),
-                };
-
-                let config_msg = ResponsePayload::Status(status_with_optional_urls);
+                let config_msg = ResponsePayload::Status(Box::new(status_with_optional_urls));
                 let config_json = serde_json::to_string(&config_msg).unwrap();
+                // Sin suscriptores `send` falla, y no importa
+                let _ = events_clone.send(config_json.clone());
 
-                let mut to_remove = vec![];
-
-                {
-                    let clients = clients_clone.lock().await;
-                    for (id, client_mutex) in clients.iter() {
-                        let mut ws = client_mutex.lock().await;
-                        if let Err(e) = ws.send(config_json.clone().into()).await {
-                            eprintln!("❌ Error al enviar mensaje a {}: {}", id, e);
-                            to_remove.push(id.clone());
+                // Un cliente con la cola llena se salta este estado (el siguiente lo
+                // sustituye); si sigue atascado más allá del timeout, se desconecta.
+                // Se mide en tiempo: los avisos de `status_changed` también disparan el bucle.
+                let stall_timeout = Duration::from_secs(service_clone.config().lock().await.server.pong_timeout_secs);
+                let mut clients = clients_clone.lock().await;
+                clients.retain(|id, client| {
+                    match client.queue.try_send(Message::Text(config_json.clone().into())) {
+                        Ok(()) => {
+                            client.last_delivered = Instant::now();
+                            true
+                        }
+                        Err(TrySendError::Full(_)) => {
+                            if client.last_delivered.elapsed() > stall_timeout {
+                                println!("🐢 Cliente-{} eliminado por no leer mensajes.", id);
+                                client.kick.notify_one();
+                                false
+                            } else {
+                                true
+                            }
+                        }
+                        Err(TrySendError::Closed(_)) => {
+                            println!("👋 Cliente-{} eliminado por desconexión.", id);
+                            false
                         }
                     }
-                }
+                });
+                drop(clients);
 
-                if !to_remove.is_empty() {
-                    let mut clients = clients_clone.lock().await;
-                    for id in to_remove {
-                        clients.remove(&id);
-                        println!("👋 Cliente {} eliminado por desconexión.", id);
-                    }
+                // Tras un comando que cambia el estado se difunde ya, sin esperar al tick
+                tokio::select! {
+                    _ = tokio::time::sleep(tokio::time::Duration::from_secs(1)) => {}
+                    _ = status_changed.notified() => {}
                 }
-
-                tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
             }
         });
 
 
+    // 💤 Detección de ausencia
+    let idle_watcher = {
+        let service = service.clone();
+        tokio::spawn(async move { service.watch_idle().await })
+    };
+
+    // 🌙 Suspensiones y bloqueos de pantalla
+    let power_watcher = {
+        let service = service.clone();
+        tokio::spawn(async move { service.watch_power().await })
+    };
+
+    // 📅 Agenda semanal
+    let schedule_watcher = {
+        let service = service.clone();
+        tokio::spawn(async move { service.watch_schedule().await })
+    };
+
+    // 🌐 API HTTP con el mismo despachador de comandos
+    let http_server = {
+        let service = service.clone();
+        let token = Arc::clone(&token);
+        let events = events.clone();
+        tokio::spawn(async move {
+            if let Err(e) = start_http_server(service, token, events).await {
+                eprintln!("❌ Error en el servidor HTTP: {}", e);
+            }
+        })
+    };
+
+    // 🧦 Socket Unix para la CLI y herramientas locales
+    let unix_server = {
+        let service = service.clone();
+        tokio::spawn(async move {
+            if let Err(e) = start_unix_server(service, unix_listener).await {
+                eprintln!("❌ Error en el socket Unix: {}", e);
+            }
+        })
+    };
+
     // 🖧 WebSocket server loop
     let listener = TcpListener::bind("127.0.0.1:9001").await?;
     println!("🖧 WebSocket Pomodoro server on ws://127.0.0.1:9001");
 
+    let mut shutdown = service.shutdown_signal();
     loop {
-        let (stream, _) = listener.accept().await?;
-        let config = Arc::clone(&config);
-        let pomodoro_handle = Arc::clone(&pomodoro_handle);
+        let (stream, _) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = shutdown.wait_for(|stop| *stop) => break,
+        };
+        let service = service.clone();
         let clients = Arc::clone(&clients);
+        let token = Arc::clone(&token);
         task::spawn(async move {
-            if let Err(e) = handle_connection(stream, config, pomodoro_handle, clients).await {
+            if let Err(e) = handle_connection(stream, service, clients, token).await {
                 eprintln!("❌ Error en conexión: {}", e);
             }
         });
     }
+    drop(listener);
+
+    // 📣 Cada conexión avisa a su cliente y cierra su socket; esperar a que terminen
+    let deadline = Instant::now() + Duration::from_secs(3);
+    while !clients.lock().await.is_empty() && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    service.shutdown().await;
+    let _ = tokio::join!(http_server, unix_server, idle_watcher, power_watcher, schedule_watcher);
+    println!("👋 Servidor detenido");
+    Ok(())
 }
 
-#[derive(Debug, Deserialize)]
-#[serde(tag = "command", rename_all = "lowercase")]
-enum ClientCommand {
-    Start,
-    Pause,
-    Resume,
-    Status,
-    MyConfig,
-    ResetProgress,
-    ResetConfig,
-    Test,
-    Help,
-    Block { url: String },
-    Unblock { url: String },
-    UpdateConfig { new_config: PomodoroStateConfig },
-    ListBlocked,
+async fn wait_for_signal() -> std::io::Result<()> {
+    let mut terminate = signal(SignalKind::terminate())?;
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => result,
+        _ = terminate.recv() => Ok(()),
+    }
 }
 
+// El tipo de error del callback lo impone tungstenite
+#[allow(clippy::result_large_err)]
 async fn handle_connection(
     stream: TcpStream,
-    config: Arc<AsyncMutex<PomodoroStateConfig>>,
-    pomodoro_handle: Arc<AsyncMutex<PomodoroHandle>>,
+    service: PomodoroService,
     clients: Clients,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let ws_stream = accept_async(stream).await?;
+    token: Arc<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let allowed_origins = read_allowed_origins();
+    let mut authenticated = false;
+    let ws_stream = accept_hdr_async(stream, |req: &Request, response: Response| {
+        let origin = req.headers().get("origin").and_then(|v| v.to_str().ok());
+        if !is_origin_allowed(origin, &allowed_origins) {
+            return Err(reject(StatusCode::FORBIDDEN, "Origin not allowed"));
+        }
+        if let Some(given) = token_from_request(req) {
+            if !tokens_match(&token, &given) {
+                return Err(reject(StatusCode::UNAUTHORIZED, "Invalid token"));
+            }
+            authenticated = true;
+        }
+        Ok(response)
+    })
+    .await?;
     println!("🔌 Cliente WebSocket conectado");
 
+    let settings = service.config().lock().await.server.clone();
+    let pong_timeout = Duration::from_secs(settings.pong_timeout_secs);
     let (write, mut read) = ws_stream.split();
+    let (queue, pending) = mpsc::channel(settings.client_queue_size.max(1));
+    let writer = task::spawn(client_writer(write, pending, pong_timeout));
+
+    let client_id = NEXT_CLIENT_ID.fetch_add(1, Ordering::SeqCst);
+    let kick = Arc::new(Notify::new());
+    let register = |clients: &mut HashMap<usize, Client>| {
+        clients.insert(client_id, Client { queue: queue.clone(), last_delivered: Instant::now(), kick: Arc::clone(&kick) });
+    };
+    // Solo los clientes autenticados reciben el estado cada segundo
+    if authenticated {
+        register(&mut *clients.lock().await);
+    }
+
+    let mut ping = tokio::time::interval(Duration::from_secs(settings.ping_interval_secs.max(1)));
+    let mut last_seen = Instant::now();
+    let mut shutdown = service.shutdown_signal();
+    let mut shutting_down = false;
+
+    let result = loop {
+        let msg = tokio::select! {
+            msg = read.next() => msg,
+            _ = ping.tick() => {
+                if last_seen.elapsed() > pong_timeout {
+                    println!("⌛ Cliente-{} sin respuesta, desconectado.", client_id);
+                    break Ok(());
+                }
+                if queue.send(Message::Ping(Default::default())).await.is_err() {
+                    break Ok(());
+                }
+                continue;
+            }
+            _ = kick.notified() => break Ok(()),
+            // El `Ref` de `wait_for` no es `Send`: se suelta dentro del bloque
+            _ = async { let _ = shutdown.wait_for(|stop| *stop).await; } => {
+                shutting_down = true;
+                break Ok(());
+            }
+        };
 
-    let client_id = format!("Client-{}", clients.lock().await.len() + 1);
-    let client = Arc::new(AsyncMutex::new(write));
-    clients.lock().await.insert(client_id.clone(), client.clone());
+        let msg = match msg {
+            Some(Ok(msg)) => msg,
+            Some(Err(e)) => break Err(e.into()),
+            None => break Ok(()),
+        };
+        // Cualquier mensaje, no solo el pong, demuestra que el cliente sigue vivo
+        last_seen = Instant::now();
 
-    while let Some(msg) = read.next().await {
-        let msg = msg?;
         if msg.is_text() {
             let input = msg.to_text()?.trim();
-    
-            let command_result: Result<ClientCommand, _> = serde_json::from_str(input);
-            let response: ResponsePayload = match command_result {
-                Ok(command) => {
-                    let mut handle = pomodoro_handle.lock().await;
-                    match command {
-                        ClientCommand::Start => {
-                            handle.start(config.clone());
-                            ResponsePayload::Message("✅ Timer started".into())
-                        }
-                        ClientCommand::Pause => {
-                            handle.pause(config.clone()).await;
-                            ResponsePayload::Message("⏸ Timer paused".into())
-                        }
-                        ClientCommand::Resume => {
-                            handle.resume(config.clone()).await;
-                            ResponsePayload::Message("▶️ Timer resumed".into())
-                        }
-                        ClientCommand::Status => {
-                            let cfg = config.lock().await.clone();
-                            let status = StatusWithOptionalUrls {
-                                config: cfg,
-                                blocked_urls: None,
-                            };
-                            ResponsePayload::Status(status)
-                        }
-                        ClientCommand::MyConfig => {
-                            let path = config_path();
-                            if std::path::Path::new(&path).exists() {
-                                ResponsePayload::Message(format!("🗂 Config file found at: {}", path))
-                            } else {
-                                ResponsePayload::Error("❌ Config file not found".into())
-                            }
-                        }
-                        ClientCommand::ResetProgress => {
-                            print!("🔄 Progress reset");
-                            handle.reset_progress(config.clone()).await;
-                            ResponsePayload::Message("🔄 Progress reset".into())
-                        }
-                        ClientCommand::ResetConfig => {
-                            handle.reset(config.clone()).await;
-                            ResponsePayload::Message("🔄 Config set to default".into())
-                        }
-                        ClientCommand::Test => {
-                            play_sound("kuru-kuru-herta-made-with-Voicemod.mp3");
-                            ResponsePayload::Message("🔊 Test sound played".into())
-                        }
-                        ClientCommand::Help => {
-                            ResponsePayload::Help(
-                                r#"
-    🆘 Available Commands:
-    { "command": "start" }               Start the Pomodoro timer
-    { "command": "pause" }               Pause the timer
-    { "command": "resume" }              Resume the timer
-    { "command": "status" }              Show current timer status
-    { "command": "myconfig" }            Show config file location
-    { "command": "reset_progress" }      Reset progress
-    { "command": "reset_config" }        Reset entire config
-    { "command": "test" }                Play test sound
-    { "command": "block", "url": "..." } Block a website
-    { "command": "help" }                Show this help message
-    "#
-                                .into(),
-                            )
-                        }
-                        ClientCommand::Block { url } => {
-                            add_url(&url).await;
-                            ResponsePayload::Message(format!("🔒 URL {} add to the blocked file", url))
-                        },
-                        ClientCommand::Unblock { url } => {
-                            remove_url(&url).await;
-                            ResponsePayload::Message(format!("🔓 URL {} removed from the blocked file", url))
-                        }
-                        ClientCommand::ListBlocked => {
-                            match list_urls().await {
-                                Ok(blocked_list) => ResponsePayload::List(blocked_list),
-                                Err(e) => ResponsePayload::Error(format!("❌ Failed to list blocked URLs: {}", e)),
-                            }
-                        },
-                        ClientCommand::UpdateConfig { new_config } => {
-                            let mut cfg = config.lock().await;
-                            *cfg = new_config.clone();
-                            cfg.save_config().await.unwrap();
-                            ResponsePayload::Message("✅ Config updated".into())
-                        }
+            let mut close_after = false;
+
+            let request_result: Result<TimerRequest, _> = serde_json::from_str(input);
+            let response: ResponsePayload = match request_result {
+                Ok(TimerRequest { command: ClientCommand::Auth { token: given }, .. }) if !authenticated => {
+                    if tokens_match(&token, &given) {
+                        authenticated = true;
+                        register(&mut *clients.lock().await);
+                        ResponsePayload::Authenticated
+                    } else {
+                        close_after = true;
+                        ApiError::forbidden("invalid token").into()
                     }
                 }
-                Err(e) => ResponsePayload::Error(format!("❌ Comando inválido: {}", e)),
+                Ok(_) if !authenticated => {
+                    close_after = true;
+                    ApiError::forbidden("authentication required").into()
+                }
+                Ok(request) => service.execute_on(request.timer.as_deref(), request.command).await,
+                Err(e) => ApiError::invalid(e.to_string()).into(),
             };
 
             let response_text = serde_json::to_string(&response)?;
-            client.lock().await.send(Message::Text(response_text.into())).await?;
+            if queue.send(Message::Text(response_text.into())).await.is_err() || close_after {
+                break Ok(());
+            }
         }
+    };
+
+    clients.lock().await.remove(&client_id);
+    if shutting_down {
+        let farewell = serde_json::to_string(&ResponsePayload::ServerShuttingDown)?;
+        let _ = queue.send(Message::Text(farewell.into())).await;
     }
+    let _ = queue.send(Message::Close(None)).await;
+    drop(queue);
+    let _ = writer.await;
+    result
+}
 
-    Ok(())
+/// Vacía la cola de un cliente en su socket. Si un envío tarda más que el
+/// timeout, el cliente se da por perdido y la tarea termina.
+async fn client_writer(mut sink: WsSink, mut pending: mpsc::Receiver<Message>, send_timeout: Duration) {
+    while let Some(msg) = pending.recv().await {
+        let closing = msg.is_close();
+        match timeout(send_timeout, sink.send(msg)).await {
+            Ok(Ok(())) if !closing => {}
+            Ok(Ok(())) => return,
+            Ok(Err(e)) => {
+                eprintln!("❌ Error al enviar mensaje: {}", e);
+                return;
+            }
+            Err(_) => {
+                eprintln!("⌛ Envío bloqueado, cerrando cliente");
+                return;
+            }
+        }
+    }
+    let _ = timeout(send_timeout, sink.close()).await;
+}
+
+fn reject(status: StatusCode, reason: &str) -> ErrorResponse {
+    let mut response = ErrorResponse::new(Some(reason.to_string()));
+    *response.status_mut() = status;
+    response
 }
@@ -0,0 +1,1351 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use tokio::sync::{mpsc, watch, Mutex as AsyncMutex, Notify};
+use tokio::time::{sleep, Duration};
+
+use crate::clock::{Clock, SystemClock};
+use crate::blocked_sites::{add_url, blocked_sites_path, list_urls, read_urls, remove_url, save_urls, stage_urls, validate_url};
+use crate::config::{config_path, PomodoroStateConfig, ValidationError, MAX_DURATION_SECS};
+use crate::config::{AwayPeriod, IdlePolicy, PauseCause, Phase, SuspendPolicy, SuspendSource, TimerState};
+use crate::export::{self, completed_sessions, import_records};
+use crate::history::{self, HistoryEvent, HistoryRange, SessionEvent, SessionEventKind};
+use crate::idle::{source_from_settings, IdleSource};
+use crate::pomodoro::PomodoroHandle;
+use crate::profiles::{apply_profile, preview_profile, read_profiles, save_profiles, validate_profile_name, PendingProfile};
+use crate::tasks::{next_task_id, read_tasks, save_tasks, validate_task, Task, TasksLock};
+use crate::suspend::{watch_logind, watch_wall_clock, PowerEvent};
+use crate::protocol::{ApiError, ClientCommand, ResponsePayload, StatusWithOptionalUrls, TimerSummary};
+use crate::protocol::{COMMAND_NAMES, MAIN_TIMER_ID};
+use crate::schedule::{in_window, ScheduleAction, Scheduler};
+use crate::stats;
+use crate::utils::{DesktopNotifier, Notifier, SiteBlocker};
+
+/// Cada cuánto se consulta la fuente de inactividad
+const IDLE_POLL_SECS: u64 = 5;
+
+/// Cada cuánto se consulta la agenda
+const SCHEDULE_POLL_SECS: u64 = 20;
+
+/// Longitud máxima del identificador de un temporizador secundario
+const MAX_TIMER_ID_LEN: usize = 32;
+
+/// Temporizador secundario: una sola fase, sin bloqueo de sitios y sin
+/// guardarse en disco.
+struct SideTimer {
+    config: Arc<AsyncMutex<PomodoroStateConfig>>,
+    handle: PomodoroHandle,
+}
+
+/// Lógica de todos los comandos, independiente del transporte. El WebSocket,
+/// la API HTTP, el socket Unix o un programa que use la librería solo tienen
+/// que traducir su formato a `ClientCommand` y devolver el `ResponsePayload`.
+#[derive(Clone)]
+pub struct PomodoroService {
+    config: Arc<AsyncMutex<PomodoroStateConfig>>,
+    pomodoro_handle: Arc<AsyncMutex<PomodoroHandle>>,
+    blocker: Arc<SiteBlocker>,
+    shutdown: watch::Sender<bool>,
+    status_changed: Arc<Notify>,
+    /// Sustituye a la fuente configurada en `idle.source`
+    idle_source: Option<Arc<dyn IdleSource>>,
+    clock: Arc<dyn Clock>,
+    notifier: Arc<dyn Notifier>,
+    timers: Arc<AsyncMutex<BTreeMap<String, SideTimer>>>,
+    /// Compartido con el temporizador, que suma pomodoros a las tareas
+    tasks: TasksLock,
+}
+
+impl PomodoroService {
+    pub fn new(config: PomodoroStateConfig) -> Self {
+        let blocker = Arc::new(SiteBlocker::new(None));
+        Self {
+            config: Arc::new(AsyncMutex::new(config)),
+            pomodoro_handle: Arc::new(AsyncMutex::new(PomodoroHandle::new(Arc::clone(&blocker)))),
+            blocker,
+            shutdown: watch::channel(false).0,
+            status_changed: Arc::new(Notify::new()),
+            idle_source: None,
+            clock: Arc::new(SystemClock),
+            notifier: Arc::new(DesktopNotifier),
+            timers: Arc::new(AsyncMutex::new(BTreeMap::new())),
+            tasks: TasksLock::default(),
+        }
+    }
+
+    /// Usa `blocker` en lugar del archivo hosts del sistema.
+    pub fn with_blocker(mut self, blocker: Arc<SiteBlocker>) -> Self {
+        self.blocker = blocker;
+        self.pomodoro_handle = Arc::new(AsyncMutex::new(self.new_handle()));
+        self
+    }
+
+    /// Usa `source` en lugar de la fuente de inactividad de la configuración.
+    pub fn with_idle_source(mut self, source: Arc<dyn IdleSource>) -> Self {
+        self.idle_source = Some(source);
+        self
+    }
+
+    /// Usa `clock` en lugar de la hora del sistema, en la agenda y en el
+    /// temporizador. Como `with_notifier`, se llama antes de arrancar nada.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self.pomodoro_handle = Arc::new(AsyncMutex::new(self.new_handle()));
+        self
+    }
+
+    /// Usa `notifier` en lugar de las notificaciones de escritorio.
+    pub fn with_notifier(mut self, notifier: Arc<dyn Notifier>) -> Self {
+        self.notifier = notifier;
+        self.pomodoro_handle = Arc::new(AsyncMutex::new(self.new_handle()));
+        self
+    }
+
+    fn new_handle(&self) -> PomodoroHandle {
+        PomodoroHandle::new(Arc::clone(&self.blocker))
+            .with_clock(Arc::clone(&self.clock))
+            .with_notifier(Arc::clone(&self.notifier))
+            .with_tasks_lock(self.tasks.clone())
+    }
+
+    pub fn config(&self) -> Arc<AsyncMutex<PomodoroStateConfig>> {
+        Arc::clone(&self.config)
+    }
+
+    /// Pide a todos los transportes que dejen de aceptar conexiones.
+    pub fn request_shutdown(&self) {
+        self.shutdown.send_replace(true);
+    }
+
+    pub fn shutdown_signal(&self) -> watch::Receiver<bool> {
+        self.shutdown.subscribe()
+    }
+
+    /// Se notifica tras cada comando que cambia algo, para que los clientes
+    /// reciban el estado nuevo sin esperar al siguiente tick.
+    pub fn status_changed(&self) -> Arc<Notify> {
+        Arc::clone(&self.status_changed)
+    }
+
+    /// Detiene el temporizador guardando su estado y levanta los bloqueos
+    /// que hayan quedado en el archivo hosts.
+    pub async fn shutdown(&self) {
+        for timer in self.timers.lock().await.values_mut() {
+            timer.handle.stop(timer.config.clone()).await;
+        }
+        self.pomodoro_handle.lock().await.stop(self.config()).await;
+        if self.blocker.is_blocking() {
+            self.blocker.unblock();
+        }
+    }
+
+    /// Consulta la fuente de inactividad hasta que se apaga el servidor.
+    pub async fn watch_idle(&self) {
+        let mut shutdown = self.shutdown_signal();
+        loop {
+            tokio::select! {
+                _ = sleep(Duration::from_secs(IDLE_POLL_SECS)) => {}
+                _ = shutdown.wait_for(|stop| *stop) => return,
+            }
+
+            let settings = self.config.lock().await.idle.clone();
+            let Some(source) = self.idle_source.clone().or_else(|| source_from_settings(&settings)) else {
+                continue;
+            };
+            let idle = tokio::task::spawn_blocking(move || source.idle_seconds()).await.ok().flatten();
+            if let Some(idle) = idle {
+                self.check_idle(idle).await;
+            }
+        }
+    }
+
+    /// Aplica la política de ausencia con `idle_secs` sin actividad. Solo
+    /// cuentan las fases de trabajo en marcha.
+    pub async fn check_idle(&self, idle_secs: u64) {
+        let mut handle = self.pomodoro_handle.lock().await;
+        let mut cfg = self.config.lock().await;
+        let threshold = u64::from(cfg.idle.threshold_secs);
+        let idle = idle_secs.min(u64::from(u32::MAX)) as u32;
+        let policy = cfg.idle.policy;
+        let paused_by_idle = cfg.paused_by == Some(PauseCause::Idle);
+
+        // (pausar, reanudar): con `Pause`, lo pausado por la ausencia se
+        // reanuda al volver
+        let (pause, resume) = match &mut cfg.away {
+            None => {
+                if idle_secs < threshold || cfg.state != TimerState::Running || cfg.current_phase != Phase::Work {
+                    return;
+                }
+                let (phase, cycle) = (cfg.current_phase, cfg.current_cycle);
+                cfg.away = Some(AwayPeriod {
+                    started_at: self.clock.now().timestamp() - i64::from(idle),
+                    seconds: idle,
+                    phase,
+                    cycle,
+                    returned: false,
+                });
+                println!("💤 Ausencia detectada ({}s sin actividad)", idle);
+                (policy == IdlePolicy::Pause, false)
+            }
+            Some(away) if !away.returned => {
+                if idle_secs >= threshold {
+                    // Con `Pause` el reloj ya no corre: la ausencia contada no crece
+                    if policy == IdlePolicy::MarkAway {
+                        away.seconds = away.seconds.max(idle);
+                    }
+                    return;
+                }
+                away.returned = true;
+                self.notifier.notify(
+                    "👋 Has vuelto",
+                    "¿Conservar o descartar el tiempo inactivo? (resolveaway)",
+                );
+                (false, paused_by_idle)
+            }
+            Some(_) => return,
+        };
+
+        let _ = cfg.save_config().await;
+        drop(cfg);
+        if pause && handle.pause(self.config()).await.is_ok() {
+            let mut cfg = self.config.lock().await;
+            cfg.paused_by = Some(PauseCause::Idle);
+            let _ = cfg.save_config().await;
+        } else if resume {
+            let _ = handle.resume(self.config()).await;
+        }
+        self.status_changed.notify_one();
+    }
+
+    /// Escucha suspensiones y bloqueos con la fuente de `suspend.source`
+    /// hasta que se apaga el servidor.
+    pub async fn watch_power(&self) {
+        let source = self.config.lock().await.suspend.source;
+        let (events, mut received) = mpsc::channel(8);
+        let watcher = match source {
+            SuspendSource::WallClock => tokio::spawn(watch_wall_clock(events)),
+            SuspendSource::Logind => tokio::spawn(async move {
+                if let Err(e) = watch_logind(events).await {
+                    eprintln!("❌ Error al escuchar logind: {}", e);
+                }
+            }),
+            SuspendSource::Disabled => return,
+        };
+
+        let mut shutdown = self.shutdown_signal();
+        loop {
+            let event = tokio::select! {
+                event = received.recv() => event,
+                _ = shutdown.wait_for(|stop| *stop) => None,
+            };
+            let Some(event) = event else {
+                break;
+            };
+            self.handle_power_event(event).await;
+        }
+        watcher.abort();
+    }
+
+    /// Aplica `suspend.policy` y `suspend.pause_on_lock`. Solo se reanuda lo
+    /// que pausó el propio servidor.
+    pub async fn handle_power_event(&self, event: PowerEvent) {
+        let mut handle = self.pomodoro_handle.lock().await;
+        let mut cfg = self.config.lock().await;
+        let settings = cfg.suspend.clone();
+
+        let cause = match event {
+            PowerEvent::Sleeping if settings.policy == SuspendPolicy::Pause => PauseCause::Suspend,
+            PowerEvent::Locked if settings.pause_on_lock => PauseCause::Lock,
+            PowerEvent::Sleeping | PowerEvent::Locked => return,
+            PowerEvent::Woke { slept_secs } => {
+                let slept = slept_secs.min(u64::from(u32::MAX)) as u32;
+                let resume = cfg.paused_by == Some(PauseCause::Suspend);
+                let active = cfg.state == TimerState::Running || resume;
+                // Mientras duerme, el temporizador no avanza: con `Count` se
+                // descuenta de golpe, sin pasar de la fase actual
+                let counted = settings.policy == SuspendPolicy::Count && cfg.state == TimerState::Running;
+                if counted {
+                    if cfg.is_counting_up() {
+                        cfg.flow_elapsed = cfg.flow_elapsed.saturating_add(slept);
+                    } else {
+                        cfg.time_left = cfg.remaining().saturating_sub(slept).max(1);
+                    }
+                    cfg.phase_elapsed = cfg.phase_elapsed.saturating_add(slept);
+                }
+                if active {
+                    println!("🌙 Equipo suspendido {}s", slept);
+                    let kind = SessionEventKind::Suspended { seconds: slept, counted };
+                    let event = SessionEvent::new(self.clock.now().timestamp(), cfg.current_phase, kind.clone());
+                    cfg.push_event(event);
+                    history::record(&cfg, &*self.clock, HistoryEvent::Adjusted(kind)).await;
+                    let _ = cfg.save_config().await;
+                }
+                drop(cfg);
+                if resume {
+                    let _ = handle.resume(self.config()).await;
+                }
+                self.status_changed.notify_one();
+                return;
+            }
+            PowerEvent::Unlocked => {
+                let resume = cfg.paused_by == Some(PauseCause::Lock);
+                drop(cfg);
+                if resume {
+                    let _ = handle.resume(self.config()).await;
+                    self.status_changed.notify_one();
+                }
+                return;
+            }
+        };
+
+        if cfg.state != TimerState::Running {
+            return;
+        }
+        drop(cfg);
+        if handle.pause(self.config()).await.is_ok() {
+            let mut cfg = self.config.lock().await;
+            cfg.paused_by = Some(cause);
+            let _ = cfg.save_config().await;
+            self.status_changed.notify_one();
+        }
+    }
+
+    /// Sigue la agenda semanal hasta que se apaga el servidor.
+    pub async fn watch_schedule(&self) {
+        let mut scheduler = Scheduler::new();
+        let mut shutdown = self.shutdown_signal();
+        loop {
+            self.check_schedule(&mut scheduler).await;
+            tokio::select! {
+                _ = sleep(Duration::from_secs(SCHEDULE_POLL_SECS)) => {}
+                _ = shutdown.wait_for(|stop| *stop) => return,
+            }
+        }
+    }
+
+    /// Aplica lo que pida la agenda a la hora de `clock`: arrancar una
+    /// sesión, terminar el día o poner y quitar el bloqueo de su ventana.
+    pub async fn check_schedule(&self, scheduler: &mut Scheduler) {
+        let now = self.clock.now().naive_local();
+        let mut handle = self.pomodoro_handle.lock().await;
+        let mut cfg = self.config.lock().await;
+        let settings = cfg.schedule.clone();
+
+        let action = scheduler.poll(&settings, now);
+        match action {
+            Some(ScheduleAction::StartSession { cycles }) => {
+                if matches!(cfg.state, TimerState::Running | TimerState::Paused | TimerState::WaitingForConfirmation) {
+                    println!("📅 Sesión programada omitida: ya hay una en curso");
+                } else {
+                    cfg.reset_progress();
+                    cfg.session_cycles = cycles;
+                    drop(cfg);
+                    println!("📅 Sesión programada");
+                    let _ = handle.start(self.config()).await;
+                    cfg = self.config.lock().await;
+                }
+            }
+            Some(ScheduleAction::EndOfDay) => {
+                drop(cfg);
+                println!("📅 Fin de la jornada");
+                handle.reset_progress(self.config()).await;
+                self.blocker.set_held(false);
+                scheduler.blocking = false;
+                if self.blocker.is_blocking() {
+                    self.blocker.unblock();
+                }
+                self.notifier.notify("📅 Fin de la jornada", "Temporizador parado y webs desbloqueadas");
+                cfg = self.config.lock().await;
+            }
+            None => {}
+        }
+
+        let window = settings.block_sites && in_window(&settings, now);
+        let entering_or_leaving = window != scheduler.blocking;
+        if window && !scheduler.blocking {
+            scheduler.blocking = true;
+            self.blocker.set_held(true);
+            if !self.blocker.is_blocking() {
+                self.blocker.block();
+            }
+        } else if !window && scheduler.blocking {
+            scheduler.blocking = false;
+            self.blocker.set_held(false);
+            // Si la fase en curso bloquea, el bloqueo sigue hasta que acabe
+            let step_blocks = cfg.state == TimerState::Running && cfg.current_step().is_some_and(|step| step.block_sites);
+            if !step_blocks && self.blocker.is_blocking() {
+                self.blocker.unblock();
+            }
+        }
+        drop(cfg);
+        if action.is_some() || entering_or_leaving {
+            self.status_changed.notify_one();
+        }
+    }
+
+    pub async fn status(&self, with_urls: bool) -> StatusWithOptionalUrls {
+        let config = self.config.lock().await.clone();
+        let blocked_urls = if with_urls {
+            match list_urls().await {
+                Ok(urls) => Some(urls),
+                Err(e) => {
+                    eprintln!("❌ Error al listar URLs: {}", e);
+                    Some(vec![])
+                }
+            }
+        } else {
+            None
+        };
+
+        let mut timers = Vec::new();
+        for (id, timer) in self.timers.lock().await.iter() {
+            let cfg = timer.config.lock().await;
+            timers.push(TimerSummary {
+                id: id.clone(),
+                label: cfg.timer_label.clone().unwrap_or_default(),
+                phase: cfg.current_phase,
+                state: cfg.state,
+                time_left: cfg.remaining(),
+            });
+        }
+
+        let task = match config.active_task {
+            Some(id) => self.read_tasks().await.into_iter().find(|task| task.id == id),
+            None => None,
+        };
+
+        StatusWithOptionalUrls { task, timers, ..StatusWithOptionalUrls::new(config, blocked_urls) }
+    }
+
+    /// Como `execute`, pero sobre el temporizador `timer`. Sin él, o con
+    /// `"main"`, actúa sobre el principal. Los secundarios solo aceptan los
+    /// comandos del temporizador y `status`.
+    pub async fn execute_on(&self, timer: Option<&str>, command: ClientCommand) -> ResponsePayload {
+        let id = match timer {
+            None | Some(MAIN_TIMER_ID) => return self.execute(command).await,
+            Some(id) => id,
+        };
+
+        let mut timers = self.timers.lock().await;
+        let Some(timer) = timers.get_mut(id) else {
+            return ApiError::not_found(format!("timer {} does not exist", id)).into();
+        };
+        let response = match timer_command(&mut timer.handle, &timer.config, command).await {
+            Ok(response) => response,
+            Err(ClientCommand::Status) => {
+                let config = timer.config.lock().await.clone();
+                ResponsePayload::Status(Box::new(StatusWithOptionalUrls::new(config, None)))
+            }
+            Err(_) => return ApiError::invalid("command not available for side timers").into(),
+        };
+        drop(timers);
+        self.status_changed.notify_one();
+        response
+    }
+
+    pub async fn execute(&self, command: ClientCommand) -> ResponsePayload {
+        let changes_state = !command.is_read_only();
+        let response = self.dispatch(command).await;
+        if changes_state {
+            self.status_changed.notify_one();
+        }
+        response
+    }
+
+    async fn dispatch(&self, command: ClientCommand) -> ResponsePayload {
+        let config = &self.config;
+        let mut handle = self.pomodoro_handle.lock().await;
+        let command = match timer_command(&mut handle, config, command).await {
+            Ok(response) => return response,
+            Err(command) => command,
+        };
+        match command {
+            ClientCommand::ResolveAway { keep } => {
+                let mut cfg = config.lock().await;
+                let Some(away) = cfg.away.take() else {
+                    return ApiError::invalid("there is no away period to resolve").into();
+                };
+                // Devolver el tiempo solo si seguimos en la fase de la ausencia
+                let same_phase = away.phase == cfg.current_phase && away.cycle == cfg.current_cycle;
+                if !keep && same_phase {
+                    cfg.time_left = cfg.remaining().saturating_add(away.seconds).min(cfg.current_duration());
+                    cfg.phase_elapsed = cfg.phase_elapsed.saturating_sub(away.seconds);
+                }
+                let kind = SessionEventKind::Away { seconds: away.seconds, kept: keep };
+                cfg.push_event(SessionEvent::new(self.clock.now().timestamp(), away.phase, kind.clone()));
+                if same_phase {
+                    history::record(&cfg, &*self.clock, HistoryEvent::Adjusted(kind)).await;
+                }
+                if let Err(e) = cfg.save_config().await {
+                    return ApiError::io(e).into();
+                }
+                ResponsePayload::AwayResolved { seconds: away.seconds, kept: keep }
+            }
+            ClientCommand::Status => ResponsePayload::Status(Box::new(self.status(false).await)),
+            ClientCommand::MyConfig => {
+                let path = config_path();
+                if std::path::Path::new(&path).exists() {
+                    ResponsePayload::ConfigPath { path }
+                } else {
+                    ApiError::io(format!("config file not found at {}", path)).into()
+                }
+            }
+            ClientCommand::ResetProgress => {
+                println!("🔄 Progreso reiniciado");
+                handle.reset_progress(config.clone()).await;
+                ResponsePayload::ProgressReset
+            }
+            ClientCommand::ResetConfig => {
+                handle.reset(config.clone()).await;
+                ResponsePayload::ConfigReset
+            }
+            ClientCommand::Test => {
+                self.notifier.play_sound("kuru-kuru-herta-made-with-Voicemod.mp3");
+                ResponsePayload::SoundPlayed
+            }
+            ClientCommand::Help => {
+                ResponsePayload::Help(COMMAND_NAMES.iter().map(|name| name.to_string()).collect())
+            }
+            ClientCommand::Auth { .. } => ResponsePayload::Authenticated,
+            ClientCommand::Block { url } => {
+                if let Err(e) = validate_url(&url) {
+                    return e.into();
+                }
+                match add_url(&url).await {
+                    Ok(()) => ResponsePayload::UrlBlocked { url },
+                    Err(e) => ApiError::io(e).into(),
+                }
+            }
+            ClientCommand::Unblock { url } => {
+                if let Err(e) = validate_url(&url) {
+                    return e.into();
+                }
+                match remove_url(&url).await {
+                    Ok(()) => ResponsePayload::UrlUnblocked { url },
+                    Err(e) => ApiError::io(e).into(),
+                }
+            }
+            ClientCommand::ListBlocked => {
+                match list_urls().await {
+                    Ok(blocked_list) => ResponsePayload::List(blocked_list),
+                    Err(e) => ApiError::io(e).into(),
+                }
+            },
+            ClientCommand::SaveProfile { name, profile } => {
+                if let Err(e) = validate_profile_name(&name) {
+                    return e.into();
+                }
+                let mut profile = profile;
+                profile.strip_idle_source();
+                if let Err(e) = preview_profile(&*config.lock().await, &profile) {
+                    return e.into();
+                }
+                let mut profiles = read_profiles().await;
+                profiles.insert(name.clone(), profile);
+                match save_profiles(&profiles).await {
+                    Ok(()) => ResponsePayload::ProfileSaved { name },
+                    Err(e) => ApiError::io(e).into(),
+                }
+            }
+            ClientCommand::DeleteProfile { name } => {
+                let mut profiles = read_profiles().await;
+                if profiles.remove(&name).is_none() {
+                    return ApiError::not_found(format!("profile {} does not exist", name)).into();
+                }
+                match save_profiles(&profiles).await {
+                    Ok(()) => ResponsePayload::ProfileDeleted { name },
+                    Err(e) => ApiError::io(e).into(),
+                }
+            }
+            ClientCommand::ListProfiles => ResponsePayload::Profiles(read_profiles().await),
+            ClientCommand::UseProfile { name, immediately } => {
+                let Some(profile) = read_profiles().await.remove(&name) else {
+                    return ApiError::not_found(format!("profile {} does not exist", name)).into();
+                };
+                let mut cfg = config.lock().await;
+                let in_phase = matches!(cfg.state, TimerState::Running | TimerState::Paused);
+                let response = if immediately || !in_phase {
+                    if let Err(e) = apply_profile(&mut cfg, &self.blocker, &name, &profile).await {
+                        return e.into();
+                    }
+                    cfg.pending_profile = None;
+                    ResponsePayload::ProfileApplied { name }
+                } else {
+                    // Se valida ya para no descubrir el error al final de la fase
+                    if let Err(e) = preview_profile(&cfg, &profile) {
+                        return e.into();
+                    }
+                    cfg.pending_profile = Some(PendingProfile { name: name.clone(), profile });
+                    ResponsePayload::ProfileScheduled { name }
+                };
+                if let Err(e) = cfg.save_config().await {
+                    return ApiError::io(e).into();
+                }
+                response
+            }
+            ClientCommand::AddTask { title, estimate, tags } => {
+                let _tasks = self.tasks.lock().await;
+                let mut tasks = read_tasks().await;
+                let task = Task { id: next_task_id(&tasks), title, estimate, pomodoros: 0, done: false, tags };
+                if let Err(e) = validate_task(&task) {
+                    return e.into();
+                }
+                tasks.push(task.clone());
+                match save_tasks(&tasks).await {
+                    Ok(()) => ResponsePayload::TaskAdded(task),
+                    Err(e) => ApiError::io(e).into(),
+                }
+            }
+            ClientCommand::UpdateTask { id, title, estimate, done, tags } => {
+                let guard = self.tasks.lock().await;
+                let mut tasks = read_tasks().await;
+                let Some(task) = tasks.iter_mut().find(|task| task.id == id) else {
+                    return ApiError::not_found(format!("task {} does not exist", id)).into();
+                };
+                let mut updated = task.clone();
+                if let Some(title) = title {
+                    updated.title = title;
+                }
+                if estimate.is_some() {
+                    updated.estimate = estimate;
+                }
+                if let Some(done) = done {
+                    updated.done = done;
+                }
+                if let Some(tags) = tags {
+                    updated.tags = tags;
+                }
+                if let Err(e) = validate_task(&updated) {
+                    return e.into();
+                }
+                *task = updated.clone();
+                if let Err(e) = save_tasks(&tasks).await {
+                    return ApiError::io(e).into();
+                }
+                drop(guard);
+                // Una tarea terminada deja de ser la activa
+                if updated.done {
+                    self.clear_active_task(id).await;
+                }
+                ResponsePayload::TaskUpdated(updated)
+            }
+            ClientCommand::DeleteTask { id } => {
+                let guard = self.tasks.lock().await;
+                let mut tasks = read_tasks().await;
+                let before = tasks.len();
+                tasks.retain(|task| task.id != id);
+                if tasks.len() == before {
+                    return ApiError::not_found(format!("task {} does not exist", id)).into();
+                }
+                if let Err(e) = save_tasks(&tasks).await {
+                    return ApiError::io(e).into();
+                }
+                drop(guard);
+                self.clear_active_task(id).await;
+                ResponsePayload::TaskDeleted { id }
+            }
+            ClientCommand::ListTasks => ResponsePayload::Tasks(self.read_tasks().await),
+            ClientCommand::SetTask { id } => {
+                let task = match id {
+                    Some(id) => match self.read_tasks().await.into_iter().find(|task| task.id == id) {
+                        Some(task) if task.done => return ApiError::invalid(format!("task {} is done", id)).into(),
+                        Some(task) => Some(task),
+                        None => return ApiError::not_found(format!("task {} does not exist", id)).into(),
+                    },
+                    None => None,
+                };
+                let mut cfg = config.lock().await;
+                cfg.active_task = id;
+                if let Err(e) = cfg.save_config().await {
+                    return ApiError::io(e).into();
+                }
+                ResponsePayload::TaskSet { task }
+            }
+            ClientCommand::History { from, to, filter } => match HistoryRange::parse(from.as_deref(), to.as_deref()) {
+                Ok(range) => ResponsePayload::History(history::query(range, &filter).await),
+                Err(e) => e.into(),
+            },
+            ClientCommand::ExportHistory { format, from, to, filter } => {
+                match HistoryRange::parse(from.as_deref(), to.as_deref()) {
+                    Ok(range) => {
+                        let records = history::read_history().await;
+                        let sessions = completed_sessions(&records, range, &filter, &self.read_tasks().await);
+                        ResponsePayload::HistoryExported { format, content: export::export(&sessions, format) }
+                    }
+                    Err(e) => e.into(),
+                }
+            }
+            ClientCommand::ImportHistory { sessions } => {
+                let import = match import_records(&sessions, &history::read_history().await) {
+                    Ok(import) => import,
+                    Err(e) => return e.into(),
+                };
+                if let Err(e) = history::append(&import.records).await {
+                    return ApiError::io(e).into();
+                }
+                println!("📥 {} fases importadas, {} repetidas", import.added, import.skipped);
+                ResponsePayload::HistoryImported { added: import.added, skipped: import.skipped }
+            }
+            ClientCommand::Stats { from, to, filter } => match HistoryRange::parse(from.as_deref(), to.as_deref()) {
+                Ok(range) => {
+                    let records = history::read_history().await;
+                    let daily_goal = config.lock().await.daily_goal;
+                    let stats = stats::compute(&records, &filter, range, &self.read_tasks().await, self.clock.now(), daily_goal);
+                    ResponsePayload::Stats(Box::new(stats))
+                }
+                Err(e) => e.into(),
+            },
+            ClientCommand::CreateTimer { id, label, duration } => {
+                if let Err(e) = validate_timer_id(&id) {
+                    return e.into();
+                }
+                let label = label.unwrap_or_else(|| id.clone());
+                let config = PomodoroStateConfig::side_timer(&label, duration);
+                if let Err(e) = config.validate() {
+                    return e.into();
+                }
+                let mut timers = self.timers.lock().await;
+                if timers.contains_key(&id) {
+                    return ApiError::invalid(format!("timer {} already exists", id)).into();
+                }
+                let mut timer = SideTimer {
+                    config: Arc::new(AsyncMutex::new(config)),
+                    handle: self.new_handle(),
+                };
+                if let Err(e) = timer.handle.start(timer.config.clone()).await {
+                    return ApiError::from(e).into();
+                }
+                println!("⏲ Temporizador {} creado ({}s)", id, duration);
+                timers.insert(id.clone(), timer);
+                ResponsePayload::TimerCreated { id }
+            }
+            ClientCommand::RemoveTimer { id } => {
+                let Some(mut timer) = self.timers.lock().await.remove(&id) else {
+                    return ApiError::not_found(format!("timer {} does not exist", id)).into();
+                };
+                timer.handle.stop(timer.config.clone()).await;
+                ResponsePayload::TimerRemoved { id }
+            }
+            ClientCommand::Batch { commands } => self.execute_batch(commands).await,
+            ClientCommand::Shutdown => {
+                self.request_shutdown();
+                ResponsePayload::ShutdownRequested
+            }
+            ClientCommand::UpdateConfig { new_config } => {
+                let mut cfg = config.lock().await;
+                let mut updated = cfg.clone();
+                updated.apply_patch(&new_config.settings());
+                if let Err(e) = updated.validate_change(&cfg) {
+                    return e.into();
+                }
+                if let Err(e) = updated.save_config().await {
+                    return ApiError::io(e).into();
+                }
+                *cfg = updated;
+                ResponsePayload::ConfigUpdated
+            }
+            ClientCommand::PatchConfig(patch) => {
+                let mut cfg = config.lock().await;
+                let mut patched = cfg.clone();
+                patched.apply_patch(&patch);
+                if let Err(e) = patched.validate_change(&cfg) {
+                    return e.into();
+                }
+                if let Err(e) = patched.save_config().await {
+                    return ApiError::io(e).into();
+                }
+                *cfg = patched;
+                ResponsePayload::ConfigPatched
+            }
+            ClientCommand::Start
+            | ClientCommand::Pause
+            | ClientCommand::Resume
+            | ClientCommand::Skip
+            | ClientCommand::Extend { .. }
+            | ClientCommand::Shorten { .. }
+            | ClientCommand::RestartPhase
+            | ClientCommand::Continue => unreachable!("handled by timer_command"),
+        }
+    }
+
+    async fn read_tasks(&self) -> Vec<Task> {
+        let _tasks = self.tasks.lock().await;
+        read_tasks().await
+    }
+
+    async fn clear_active_task(&self, id: u32) {
+        let mut cfg = self.config.lock().await;
+        if cfg.active_task == Some(id) {
+            cfg.active_task = None;
+            let _ = cfg.save_config().await;
+        }
+    }
+
+    /// Aplica varios comandos de configuración y lista de bloqueo como una
+    /// sola transacción: se valida el lote entero antes de tocar nada, todo
+    /// ocurre bajo un único bloqueo de la configuración y, si algo falla, no se
+    /// aplica ningún cambio.
+    async fn execute_batch(&self, commands: Vec<ClientCommand>) -> ResponsePayload {
+        if let Some(index) = commands.iter().position(|command| !command.is_batchable()) {
+            return ApiError::invalid(format!("command #{} is not allowed inside a batch", index)).into();
+        }
+
+        let mut cfg = self.config.lock().await;
+        let mut working = cfg.clone();
+        let original_urls = read_urls().await;
+        let mut urls = original_urls.clone();
+
+        for command in &commands {
+            if let ClientCommand::Block { url } | ClientCommand::Unblock { url } = command {
+                if let Err(e) = validate_url(url) {
+                    return e.into();
+                }
+            }
+        }
+
+        let mut results = Vec::with_capacity(commands.len());
+        for command in commands {
+            let result = match command {
+                ClientCommand::Status => {
+                    ResponsePayload::Status(Box::new(StatusWithOptionalUrls::new(working.clone(), None)))
+                }
+                ClientCommand::ListBlocked => ResponsePayload::List(urls.iter().cloned().collect()),
+                ClientCommand::Block { url } => {
+                    urls.insert(url.clone());
+                    ResponsePayload::UrlBlocked { url }
+                }
+                ClientCommand::Unblock { url } => {
+                    urls.remove(&url);
+                    ResponsePayload::UrlUnblocked { url }
+                }
+                ClientCommand::UpdateConfig { new_config } => {
+                    working.apply_patch(&new_config.settings());
+                    ResponsePayload::ConfigUpdated
+                }
+                ClientCommand::PatchConfig(patch) => {
+                    working.apply_patch(&patch);
+                    ResponsePayload::ConfigPatched
+                }
+                _ => unreachable!("filtered by is_batchable"),
+            };
+            results.push(result);
+        }
+
+        if let Err(e) = working.validate_change(&cfg) {
+            return e.into();
+        }
+
+        // Ambos ficheros se escriben primero a un temporal y solo se renombran si
+        // las dos escrituras salieron bien; la configuración en memoria no se
+        // toca hasta el final.
+        let config_tmp = match working.stage_config().await {
+            Ok(tmp) => tmp,
+            Err(e) => return ApiError::io(e).into(),
+        };
+        let urls_tmp = if urls != original_urls {
+            match stage_urls(&urls).await {
+                Ok(tmp) => Some(tmp),
+                Err(e) => {
+                    discard_staged(config_tmp.as_deref()).await;
+                    return ApiError::io(e).into();
+                }
+            }
+        } else {
+            None
+        };
+
+        if let Some(tmp) = &urls_tmp {
+            if let Err(e) = tokio::fs::rename(tmp, blocked_sites_path()).await {
+                discard_staged(Some(tmp)).await;
+                discard_staged(config_tmp.as_deref()).await;
+                return ApiError::io(e).into();
+            }
+        }
+        if let Some(tmp) = &config_tmp {
+            if let Err(e) = tokio::fs::rename(tmp, config_path()).await {
+                discard_staged(Some(tmp)).await;
+                if urls_tmp.is_some() {
+                    let _ = save_urls(&original_urls).await;
+                }
+                return ApiError::io(e).into();
+            }
+        }
+        *cfg = working;
+        ResponsePayload::Batch(results)
+    }
+}
+
+async fn discard_staged(tmp: Option<&str>) {
+    if let Some(tmp) = tmp {
+        let _ = tokio::fs::remove_file(tmp).await;
+    }
+}
+
+fn validate_adjustment(seconds: u32) -> Result<(), ValidationError> {
+    if seconds == 0 || seconds > MAX_DURATION_SECS {
+        return Err(ValidationError {
+            field: "seconds",
+            message: format!("must be between 1 and {} seconds", MAX_DURATION_SECS),
+        });
+    }
+    Ok(())
+}
+
+fn validate_timer_id(id: &str) -> Result<(), ValidationError> {
+    if id.trim().is_empty() || id.chars().count() > MAX_TIMER_ID_LEN {
+        return Err(ValidationError {
+            field: "id",
+            message: format!("must be between 1 and {} characters", MAX_TIMER_ID_LEN),
+        });
+    }
+    if id == MAIN_TIMER_ID {
+        return Err(ValidationError {
+            field: "id",
+            message: format!("{} is reserved for the main timer", MAIN_TIMER_ID),
+        });
+    }
+    Ok(())
+}
+
+/// Comandos que actúan sobre un temporizador concreto, sea el principal o uno
+/// secundario. Devuelve el comando sin tocar si no es de este tipo.
+async fn timer_command(
+    handle: &mut PomodoroHandle,
+    config: &Arc<AsyncMutex<PomodoroStateConfig>>,
+    command: ClientCommand,
+) -> Result<ResponsePayload, ClientCommand> {
+    let response = match command {
+        ClientCommand::Start => match handle.start(config.clone()).await {
+            Ok(()) => ResponsePayload::Started,
+            Err(e) => ApiError::from(e).into(),
+        },
+        ClientCommand::Pause => match handle.pause(config.clone()).await {
+            Ok(()) => ResponsePayload::Paused,
+            Err(e) => ApiError::from(e).into(),
+        },
+        ClientCommand::Resume => match handle.resume(config.clone()).await {
+            Ok(()) => ResponsePayload::Resumed,
+            Err(e) => ApiError::from(e).into(),
+        },
+        ClientCommand::Skip => match handle.skip(config.clone()).await {
+            Ok(phase) => ResponsePayload::PhaseSkipped { phase },
+            Err(e) => ApiError::from(e).into(),
+        },
+        ClientCommand::Extend { seconds } => {
+            if let Err(e) = validate_adjustment(seconds) {
+                return Ok(e.into());
+            }
+            match handle.extend(config.clone(), seconds).await {
+                Ok(time_left) => ResponsePayload::PhaseExtended { time_left },
+                Err(e) => ApiError::from(e).into(),
+            }
+        }
+        ClientCommand::Shorten { seconds } => {
+            if let Err(e) = validate_adjustment(seconds) {
+                return Ok(e.into());
+            }
+            match handle.shorten(config.clone(), seconds).await {
+                Ok(time_left) => ResponsePayload::PhaseShortened { time_left },
+                Err(e) => ApiError::from(e).into(),
+            }
+        }
+        ClientCommand::RestartPhase => match handle.restart_phase(config.clone()).await {
+            Ok(time_left) => ResponsePayload::PhaseRestarted { time_left },
+            Err(e) => ApiError::from(e).into(),
+        },
+        ClientCommand::Continue => match handle.continue_phase(config.clone()).await {
+            Ok(()) => ResponsePayload::Continued,
+            Err(e) => ApiError::from(e).into(),
+        },
+        command => return Err(command),
+    };
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FakeClock;
+    use crate::config::{ConfigPatch, IdleSettings, IdleSourceSettings, PhaseStep, ScheduledSession, Weekday};
+    use chrono::TimeZone;
+    use crate::idle::MockIdleSource;
+    use crate::utils::RecordingNotifier;
+
+    struct Fixture {
+        service: PomodoroService,
+        clock: Arc<FakeClock>,
+        notifier: Arc<RecordingNotifier>,
+        blocker: Arc<SiteBlocker>,
+    }
+
+    /// Servicio en memoria, con trabajo de 60 s y pausas de 10 y 20 s.
+    fn fixture(configure: impl FnOnce(&mut PomodoroStateConfig)) -> Fixture {
+        let mut cfg = PomodoroStateConfig::new();
+        cfg.in_memory = true;
+        cfg.work_duration = 60;
+        cfg.break_duration = 10;
+        cfg.long_break_duration = 20;
+        configure(&mut cfg);
+        cfg.reset_progress();
+        let clock = Arc::new(FakeClock::new(chrono::Local::now()));
+        let notifier = Arc::new(RecordingNotifier::default());
+        let blocker = Arc::new(SiteBlocker::simulated());
+        let service = PomodoroService::new(cfg)
+            .with_blocker(blocker.clone())
+            .with_clock(clock.clone())
+            .with_notifier(notifier.clone());
+        Fixture { service, clock, notifier, blocker }
+    }
+
+    fn service(configure: impl FnOnce(&mut PomodoroStateConfig)) -> PomodoroService {
+        fixture(configure).service
+    }
+
+    async fn snapshot(service: &PomodoroService) -> PomodoroStateConfig {
+        service.config().lock().await.clone()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn update_config_keeps_the_running_phase() {
+        let service = service(|_| {});
+        service.execute(ClientCommand::Start).await;
+        sleep(Duration::from_millis(5_500)).await;
+
+        let mut snapshot = service.status(false).await.config;
+        let time_left = snapshot.time_left;
+        snapshot.break_duration = 15;
+        snapshot.time_left = 1;
+        snapshot.state = TimerState::Idle;
+        let response = service.execute(ClientCommand::UpdateConfig { new_config: Box::new(snapshot) }).await;
+        assert!(matches!(response, ResponsePayload::ConfigUpdated));
+
+        let cfg = service.config().lock().await.clone();
+        assert_eq!(cfg.break_duration, 15);
+        assert_eq!(cfg.state, TimerState::Running);
+        assert_eq!(cfg.current_phase, Phase::Work);
+        assert_eq!(cfg.time_left, time_left);
+        assert_eq!(cfg.phase_elapsed, 60 - time_left);
+        service.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn idle_source_cannot_be_changed_by_clients() {
+        let service = service(|_| {});
+        let idle = IdleSettings {
+            source: IdleSourceSettings::Command { command: "touch /tmp/pwned".into() },
+            ..IdleSettings::default()
+        };
+        let patch = ConfigPatch { idle: Some(idle.clone()), ..ConfigPatch::default() };
+        let response = service.execute(ClientCommand::PatchConfig(patch)).await;
+        assert!(matches!(response, ResponsePayload::Error(ApiError::ValidationFailed { ref field, .. }) if field == "idle.source"));
+
+        let mut snapshot = service.status(false).await.config;
+        snapshot.idle = idle;
+        let response = service.execute(ClientCommand::Batch {
+            commands: vec![ClientCommand::UpdateConfig { new_config: Box::new(snapshot) }],
+        }).await;
+        assert!(matches!(response, ResponsePayload::Error(ApiError::ValidationFailed { .. })));
+        assert_eq!(service.config().lock().await.idle.source, IdleSourceSettings::Disabled);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_source_pauses_work_until_the_user_returns() {
+        let source = Arc::new(MockIdleSource::default());
+        let Fixture { service, notifier, .. } = fixture(|cfg| cfg.idle.threshold_secs = 30);
+        let service = service.with_idle_source(source.clone());
+        let watcher = tokio::spawn({
+            let service = service.clone();
+            async move { service.watch_idle().await }
+        });
+        service.execute(ClientCommand::Start).await;
+
+        source.set(40);
+        sleep(Duration::from_secs(IDLE_POLL_SECS + 1)).await;
+        let cfg = snapshot(&service).await;
+        assert_eq!((cfg.state, cfg.paused_by), (TimerState::Paused, Some(PauseCause::Idle)));
+        assert!(cfg.away.as_ref().is_some_and(|away| away.seconds == 40 && !away.returned));
+
+        source.set(0);
+        sleep(Duration::from_secs(IDLE_POLL_SECS)).await;
+        let cfg = snapshot(&service).await;
+        assert_eq!((cfg.state, cfg.paused_by), (TimerState::Running, None));
+        assert!(cfg.away.is_some_and(|away| away.returned));
+        assert!(notifier.notifications.lock().unwrap().iter().any(|title| title.contains("Has vuelto")));
+
+        service.execute(ClientCommand::ResolveAway { keep: true }).await;
+        assert_eq!(snapshot(&service).await.away, None);
+        service.request_shutdown();
+        watcher.await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn paused_away_time_can_be_given_back() {
+        let service = service(|cfg| cfg.idle.threshold_secs = 30);
+        service.execute(ClientCommand::Start).await;
+        sleep(Duration::from_millis(40_500)).await;
+
+        service.check_idle(35).await;
+        assert_eq!(snapshot(&service).await.state, TimerState::Paused);
+        // En pausa el reloj no corre: la ausencia se queda en lo detectado
+        sleep(Duration::from_secs(100)).await;
+        service.check_idle(200).await;
+        let cfg = snapshot(&service).await;
+        assert_eq!((cfg.time_left, cfg.away.as_ref().map(|away| away.seconds)), (20, Some(35)));
+
+        service.check_idle(0).await;
+        assert_eq!(snapshot(&service).await.state, TimerState::Running);
+        let response = service.execute(ClientCommand::ResolveAway { keep: false }).await;
+        assert!(matches!(response, ResponsePayload::AwayResolved { seconds: 35, kept: false }));
+        let cfg = snapshot(&service).await;
+        assert_eq!((cfg.time_left, cfg.phase_elapsed), (55, 5));
+        assert_eq!(
+            cfg.session_events.last().map(|event| &event.kind),
+            Some(&SessionEventKind::Away { seconds: 35, kept: false })
+        );
+        service.shutdown().await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn mark_away_keeps_counting_and_can_give_the_time_back() {
+        let service = service(|cfg| {
+            cfg.idle.threshold_secs = 30;
+            cfg.idle.policy = IdlePolicy::MarkAway;
+        });
+        service.execute(ClientCommand::Start).await;
+        sleep(Duration::from_millis(20_500)).await;
+
+        service.check_idle(10).await;
+        assert_eq!(snapshot(&service).await.away, None);
+        service.check_idle(30).await;
+        service.check_idle(35).await;
+        let cfg = snapshot(&service).await;
+        assert_eq!(cfg.state, TimerState::Running);
+        assert_eq!(cfg.away.as_ref().map(|away| away.seconds), Some(35));
+        let time_left = cfg.time_left;
+
+        service.check_idle(0).await;
+        service.execute(ClientCommand::ResolveAway { keep: false }).await;
+        let cfg = snapshot(&service).await;
+        assert_eq!(cfg.away, None);
+        assert_eq!(cfg.time_left, (time_left + 35).min(60));
+        assert_eq!(cfg.phase_elapsed, 60 - cfg.time_left);
+        service.shutdown().await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn breaks_are_not_checked_for_idleness() {
+        let service = service(|cfg| cfg.idle.threshold_secs = 30);
+        service.execute(ClientCommand::Start).await;
+        service.execute(ClientCommand::Skip).await;
+        service.check_idle(300).await;
+        let cfg = snapshot(&service).await;
+        assert_eq!(cfg.current_phase, Phase::ShortBreak);
+        assert_eq!(cfg.away, None);
+        service.shutdown().await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn counted_suspend_is_taken_from_the_phase() {
+        let service = service(|cfg| cfg.suspend.policy = SuspendPolicy::Count);
+        service.execute(ClientCommand::Start).await;
+        sleep(Duration::from_millis(10_500)).await;
+
+        service.handle_power_event(PowerEvent::Sleeping).await;
+        assert_eq!(snapshot(&service).await.state, TimerState::Running);
+        service.handle_power_event(PowerEvent::Woke { slept_secs: 20 }).await;
+        let cfg = snapshot(&service).await;
+        assert_eq!(cfg.time_left, 30);
+        assert_eq!(cfg.phase_elapsed, 30);
+        assert_eq!(
+            cfg.session_events.last().map(|event| &event.kind),
+            Some(&SessionEventKind::Suspended { seconds: 20, counted: true })
+        );
+
+        // Nunca se pasa de la fase actual
+        service.handle_power_event(PowerEvent::Woke { slept_secs: 600 }).await;
+        let cfg = snapshot(&service).await;
+        assert_eq!((cfg.current_phase, cfg.time_left), (Phase::Work, 1));
+        service.shutdown().await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn paused_suspend_resumes_without_losing_time() {
+        let service = service(|_| {});
+        service.execute(ClientCommand::Start).await;
+        sleep(Duration::from_millis(10_500)).await;
+
+        service.handle_power_event(PowerEvent::Sleeping).await;
+        let cfg = snapshot(&service).await;
+        assert_eq!((cfg.state, cfg.paused_by), (TimerState::Paused, Some(PauseCause::Suspend)));
+        sleep(Duration::from_secs(30)).await;
+
+        service.handle_power_event(PowerEvent::Woke { slept_secs: 30 }).await;
+        let cfg = snapshot(&service).await;
+        assert_eq!((cfg.state, cfg.paused_by), (TimerState::Running, None));
+        assert_eq!(cfg.time_left, 50);
+        assert_eq!(
+            cfg.session_events.last().map(|event| &event.kind),
+            Some(&SessionEventKind::Suspended { seconds: 30, counted: false })
+        );
+        service.shutdown().await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn only_server_pauses_are_resumed_on_unlock() {
+        let service = service(|cfg| cfg.suspend.pause_on_lock = true);
+        service.execute(ClientCommand::Start).await;
+
+        service.handle_power_event(PowerEvent::Locked).await;
+        assert_eq!(snapshot(&service).await.paused_by, Some(PauseCause::Lock));
+        service.handle_power_event(PowerEvent::Unlocked).await;
+        assert_eq!(snapshot(&service).await.state, TimerState::Running);
+
+        service.execute(ClientCommand::Pause).await;
+        service.handle_power_event(PowerEvent::Locked).await;
+        service.handle_power_event(PowerEvent::Unlocked).await;
+        let cfg = snapshot(&service).await;
+        assert_eq!((cfg.state, cfg.paused_by), (TimerState::Paused, None));
+        service.shutdown().await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn side_timers_run_apart_from_the_main_one() {
+        let service = service(|_| {});
+        let create = |id: &str| ClientCommand::CreateTimer { id: id.into(), label: None, duration: 30 };
+        assert!(matches!(service.execute(create("tea")).await, ResponsePayload::TimerCreated { .. }));
+        assert!(matches!(service.execute(create(MAIN_TIMER_ID)).await, ResponsePayload::Error(ApiError::ValidationFailed { .. })));
+
+        service.execute_on(Some("tea"), ClientCommand::Pause).await;
+        let status = service.status(false).await;
+        assert_eq!(status.config.state, TimerState::Idle);
+        assert!(matches!(status.timers.as_slice(), [timer] if timer.id == "tea" && timer.state == TimerState::Paused));
+
+        service.execute_on(Some("tea"), ClientCommand::Resume).await;
+        sleep(Duration::from_secs(31)).await;
+        let ResponsePayload::Status(tea) = service.execute_on(Some("tea"), ClientCommand::Status).await else {
+            panic!("expected the side timer status");
+        };
+        assert_eq!(tea.config.state, TimerState::Finished);
+        assert_eq!(snapshot(&service).await.state, TimerState::Idle);
+
+        let response = service.execute_on(Some("tea"), ClientCommand::ListTasks).await;
+        assert!(matches!(response, ResponsePayload::Error(ApiError::InvalidCommand { .. })));
+        let response = service.execute_on(Some("coffee"), ClientCommand::Status).await;
+        assert!(matches!(response, ResponsePayload::Error(ApiError::NotFound { .. })));
+
+        service.execute(ClientCommand::RemoveTimer { id: "tea".into() }).await;
+        assert!(service.status(false).await.timers.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn side_timers_leave_the_main_blocking_and_session_alone() {
+        let Fixture { service, blocker, .. } = fixture(|cfg| {
+            cfg.sequence = vec![PhaseStep {
+                name: "Trabajo".into(),
+                kind: Phase::Work,
+                duration: 60,
+                block_sites: true,
+                sound: None,
+                notification: None,
+                auto_start: None,
+            }];
+        });
+        service.execute(ClientCommand::Start).await;
+        sleep(Duration::from_millis(500)).await;
+        assert!(blocker.is_blocking());
+        let before = snapshot(&service).await;
+        let create = ClientCommand::CreateTimer { id: "tea".into(), label: None, duration: 30 };
+        service.execute(create).await;
+
+        let response = service.execute_on(Some("tea"), ClientCommand::RestartPhase).await;
+        assert!(matches!(response, ResponsePayload::PhaseRestarted { time_left: 30 }));
+        let response = service.execute_on(Some("tea"), ClientCommand::Skip).await;
+        assert!(matches!(response, ResponsePayload::PhaseSkipped { phase: Phase::Idle }));
+        // Los reinicios de progreso o de configuración son solo del principal
+        let response = service.execute_on(Some("tea"), ClientCommand::ResetProgress).await;
+        assert!(matches!(response, ResponsePayload::Error(ApiError::InvalidCommand { .. })));
+
+        assert!(blocker.is_blocking());
+        let cfg = snapshot(&service).await;
+        assert_eq!((cfg.state, cfg.current_phase), (TimerState::Running, Phase::Work));
+        assert_eq!(cfg.phase_session, before.phase_session);
+        assert_eq!(cfg.session_events, before.session_events);
+        service.shutdown().await;
+        assert!(!blocker.is_blocking());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn scheduled_session_overrides_cycles_until_the_end_of_day() {
+        let Fixture { service, clock, .. } = fixture(|cfg| {
+            cfg.cycles = 4;
+            cfg.final_long_break = true;
+            cfg.schedule.sessions = vec![ScheduledSession {
+                days: vec![Weekday::Mon],
+                start: "09:00".into(),
+                cycles: Some(1),
+                end: None,
+            }];
+            cfg.schedule.end_of_day = Some("18:00".into());
+        });
+        // 19 de octubre de 2026, lunes
+        let at = |hour, minute, second| chrono::Local.with_ymd_and_hms(2026, 10, 19, hour, minute, second).unwrap();
+        let mut scheduler = Scheduler::new();
+        clock.set(at(8, 59, 30));
+        service.check_schedule(&mut scheduler).await;
+        assert_eq!(snapshot(&service).await.state, TimerState::Idle);
+
+        clock.set(at(9, 0, 5));
+        service.check_schedule(&mut scheduler).await;
+        let cfg = snapshot(&service).await;
+        assert_eq!(cfg.state, TimerState::Running);
+        assert_eq!((cfg.session_cycles, cfg.cycles), (Some(1), 4));
+
+        // Un bloque de trabajo y la pausa larga final
+        sleep(Duration::from_secs(60 + 20 + 1)).await;
+        let cfg = snapshot(&service).await;
+        assert_eq!((cfg.state, cfg.current_cycle), (TimerState::Finished, 1));
+
+        clock.set(at(18, 0, 5));
+        service.check_schedule(&mut scheduler).await;
+        let cfg = snapshot(&service).await;
+        assert_eq!(cfg.state, TimerState::Idle);
+        assert_eq!((cfg.session_cycles, cfg.planned_cycles()), (None, 4));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn scheduled_session_does_not_interrupt_a_running_one() {
+        let Fixture { service, clock, .. } = fixture(|cfg| {
+            cfg.schedule.sessions = vec![ScheduledSession {
+                days: vec![Weekday::Mon],
+                start: "09:00".into(),
+                cycles: Some(1),
+                end: None,
+            }];
+        });
+        let at = |hour, minute, second| chrono::Local.with_ymd_and_hms(2026, 10, 19, hour, minute, second).unwrap();
+        let mut scheduler = Scheduler::new();
+        clock.set(at(8, 59, 50));
+        service.check_schedule(&mut scheduler).await;
+        service.execute(ClientCommand::Start).await;
+        sleep(Duration::from_millis(5_500)).await;
+
+        clock.set(at(9, 0, 5));
+        service.check_schedule(&mut scheduler).await;
+        let cfg = snapshot(&service).await;
+        assert_eq!(cfg.session_cycles, None);
+        assert_eq!(cfg.time_left, 55);
+        service.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn blocked_sites_must_be_host_names() {
+        let service = service(|_| {});
+        let url = "x'; touch /tmp/pwned; echo '".to_string();
+        let response = service.execute(ClientCommand::Block { url: url.clone() }).await;
+        assert!(matches!(response, ResponsePayload::Error(ApiError::ValidationFailed { ref field, .. }) if field == "url"));
+        let response = service.execute(ClientCommand::Batch {
+            commands: vec![ClientCommand::PatchConfig(ConfigPatch::default()), ClientCommand::Unblock { url }],
+        }).await;
+        assert!(matches!(response, ResponsePayload::Error(ApiError::ValidationFailed { ref field, .. }) if field == "url"));
+    }
+}
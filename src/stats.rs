@@ -0,0 +1,308 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Datelike, Local, NaiveDate, TimeZone, Timelike};
+use serde::{Deserialize, Serialize};
+
+use crate::config::Phase;
+use crate::history::{HistoryEvent, HistoryFilter, HistoryRange, HistoryRecord, PhaseOutcome, SessionEventKind};
+use crate::tasks::Task;
+
+/// Resumen del historial. Un pomodoro es una fase de trabajo que terminó
+/// sola (`Ended` con `Completed`); el foco son los segundos que corrieron
+/// las fases de trabajo, acabaran como acabaran.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Stats {
+    /// Pomodoros de hoy y de esta semana (desde el lunes), sin intervalo ni filtro
+    pub today: u32,
+    pub this_week: u32,
+    /// Pomodoros y foco dentro del intervalo y el filtro pedidos
+    pub pomodoros: u32,
+    pub focus_seconds: u32,
+    /// Fases de trabajo completadas entre todas las que se cerraron, de 0 a 1
+    pub completion_rate: f64,
+    /// Pausas, suspensiones y ausencias por fase de trabajo
+    pub interruptions_per_phase: f64,
+    pub daily_goal: u32,
+    /// Días seguidos, hasta hoy, con al menos `daily_goal` pomodoros. Si hoy
+    /// aún no se ha llegado, cuenta hasta ayer.
+    pub current_streak: u32,
+    pub longest_streak: u32,
+    pub tasks: Vec<TaskTotal>,
+    pub tags: Vec<TagTotal>,
+    /// Horas del día con más foco, de más a menos (como mucho tres)
+    pub best_hours: Vec<HourTotal>,
+    pub days: Vec<DayTotal>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+pub struct TaskTotal {
+    pub id: u32,
+    /// Sin título si la tarea ya no existe
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub pomodoros: u32,
+    pub focus_seconds: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+pub struct TagTotal {
+    pub tag: String,
+    pub pomodoros: u32,
+    pub focus_seconds: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct HourTotal {
+    /// Hora local en la que empezó el trabajo, de 0 a 23
+    pub hour: u32,
+    pub focus_seconds: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct DayTotal {
+    /// `AAAA-MM-DD`
+    pub date: String,
+    pub pomodoros: u32,
+    pub focus_seconds: u32,
+}
+
+const BEST_HOURS: usize = 3;
+
+/// Calcula las estadísticas de `records` (el historial completo). `filter` y
+/// `range` acotan los totales; hoy, la semana y las rachas se miran siempre
+/// en todo el historial.
+pub fn compute(
+    records: &[HistoryRecord],
+    filter: &HistoryFilter,
+    range: HistoryRange,
+    tasks: &[Task],
+    now: DateTime<Local>,
+    daily_goal: u32,
+) -> Stats {
+    let today = now.date_naive();
+    let week_start = today - chrono::Duration::days(i64::from(today.weekday().num_days_from_monday()));
+
+    let mut per_day: BTreeMap<NaiveDate, u32> = BTreeMap::new();
+    for (date, _) in work_ends(records).filter(|(_, outcome)| *outcome == PhaseOutcome::Completed) {
+        *per_day.entry(date).or_default() += 1;
+    }
+
+    let mut stats = Stats {
+        today: per_day.get(&today).copied().unwrap_or(0),
+        this_week: per_day.range(week_start..).map(|(_, count)| count).sum(),
+        pomodoros: 0,
+        focus_seconds: 0,
+        completion_rate: 0.0,
+        interruptions_per_phase: 0.0,
+        daily_goal,
+        current_streak: current_streak(&per_day, today, daily_goal),
+        longest_streak: longest_streak(&per_day, daily_goal),
+        tasks: Vec::new(),
+        tags: Vec::new(),
+        best_hours: Vec::new(),
+        days: Vec::new(),
+    };
+
+    let mut ended = 0u32;
+    let mut interruptions = 0u32;
+    let mut tasks_total: BTreeMap<u32, TaskTotal> = BTreeMap::new();
+    let mut tags_total: BTreeMap<String, TagTotal> = BTreeMap::new();
+    let mut hours: BTreeMap<u32, u32> = BTreeMap::new();
+    let mut days: BTreeMap<NaiveDate, DayTotal> = BTreeMap::new();
+    let selected = records.iter().filter(|record| filter.matches(record) && range.contains(record.at));
+    for record in selected.filter(|record| record.phase == Phase::Work) {
+        match &record.event {
+            HistoryEvent::Paused
+            | HistoryEvent::Adjusted(SessionEventKind::Suspended { .. } | SessionEventKind::Away { .. }) => {
+                interruptions += 1;
+            }
+            HistoryEvent::Ended { actual, outcome, .. } => {
+                let Some(at) = local_time(record.at) else {
+                    continue;
+                };
+                let pomodoro = u32::from(*outcome == PhaseOutcome::Completed);
+                ended += 1;
+                stats.pomodoros += pomodoro;
+                stats.focus_seconds += actual;
+
+                if let Some(id) = record.task {
+                    let total = tasks_total.entry(id).or_insert_with(|| TaskTotal { id, ..Default::default() });
+                    total.pomodoros += pomodoro;
+                    total.focus_seconds += actual;
+                }
+                for tag in &record.tags {
+                    let total = tags_total.entry(tag.clone()).or_insert_with(|| TagTotal { tag: tag.clone(), ..Default::default() });
+                    total.pomodoros += pomodoro;
+                    total.focus_seconds += actual;
+                }
+                if let Some(started) = local_time(record.at - i64::from(*actual)) {
+                    *hours.entry(started.hour()).or_default() += actual;
+                }
+                let day = days.entry(at.date_naive()).or_insert_with(|| DayTotal {
+                    date: at.format("%Y-%m-%d").to_string(),
+                    pomodoros: 0,
+                    focus_seconds: 0,
+                });
+                day.pomodoros += pomodoro;
+                day.focus_seconds += actual;
+            }
+            _ => {}
+        }
+    }
+
+    if ended > 0 {
+        stats.completion_rate = f64::from(stats.pomodoros) / f64::from(ended);
+        stats.interruptions_per_phase = f64::from(interruptions) / f64::from(ended);
+    }
+    stats.tasks = tasks_total
+        .into_values()
+        .map(|mut total| {
+            total.title = tasks.iter().find(|task| task.id == total.id).map(|task| task.title.clone());
+            total
+        })
+        .collect();
+    stats.tasks.sort_by_key(|total| Reverse(total.focus_seconds));
+    stats.tags = tags_total.into_values().collect();
+    stats.tags.sort_by_key(|total| Reverse(total.focus_seconds));
+    stats.best_hours = hours
+        .into_iter()
+        .filter(|(_, seconds)| *seconds > 0)
+        .map(|(hour, focus_seconds)| HourTotal { hour, focus_seconds })
+        .collect();
+    stats.best_hours.sort_by_key(|total| Reverse(total.focus_seconds));
+    stats.best_hours.truncate(BEST_HOURS);
+    stats.days = days.into_values().collect();
+    stats
+}
+
+/// Día local y resultado de cada fase de trabajo cerrada.
+fn work_ends(records: &[HistoryRecord]) -> impl Iterator<Item = (NaiveDate, PhaseOutcome)> + '_ {
+    records.iter().filter(|record| record.phase == Phase::Work).filter_map(|record| match record.event {
+        HistoryEvent::Ended { outcome, .. } => local_time(record.at).map(|at| (at.date_naive(), outcome)),
+        _ => None,
+    })
+}
+
+fn local_time(at: i64) -> Option<DateTime<Local>> {
+    Local.timestamp_opt(at, 0).single()
+}
+
+fn meets_goal(per_day: &BTreeMap<NaiveDate, u32>, date: NaiveDate, goal: u32) -> bool {
+    per_day.get(&date).is_some_and(|count| *count >= goal)
+}
+
+fn current_streak(per_day: &BTreeMap<NaiveDate, u32>, today: NaiveDate, goal: u32) -> u32 {
+    let mut date = if meets_goal(per_day, today, goal) {
+        Some(today)
+    } else {
+        today.pred_opt()
+    };
+    let mut streak = 0;
+    while let Some(day) = date.filter(|day| meets_goal(per_day, *day, goal)) {
+        streak += 1;
+        date = day.pred_opt();
+    }
+    streak
+}
+
+fn longest_streak(per_day: &BTreeMap<NaiveDate, u32>, goal: u32) -> u32 {
+    let mut longest = 0;
+    let mut streak = 0;
+    let mut previous: Option<NaiveDate> = None;
+    for (date, _) in per_day.iter().filter(|(_, count)| **count >= goal) {
+        streak = if previous.and_then(|day| day.succ_opt()) == Some(*date) { streak + 1 } else { 1 };
+        longest = longest.max(streak);
+        previous = Some(*date);
+    }
+    longest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2026-10-21 es miércoles
+    fn at(day: u32, hour: u32) -> i64 {
+        Local.with_ymd_and_hms(2026, 10, day, hour, 0, 0).unwrap().timestamp()
+    }
+
+    fn work(at: i64, actual: u32, outcome: PhaseOutcome, task: Option<u32>, tags: &[&str]) -> HistoryRecord {
+        HistoryRecord {
+            at,
+            session: format!("{}-Work", at - i64::from(actual)),
+            phase: Phase::Work,
+            task,
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            profile: None,
+            event: HistoryEvent::Ended { planned: 1500, actual, outcome },
+        }
+    }
+
+    fn pomodoros(day: u32, count: u32) -> Vec<HistoryRecord> {
+        (0..count).map(|i| work(at(day, 9 + i), 1500, PhaseOutcome::Completed, None, &[])).collect()
+    }
+
+    #[test]
+    fn totals_per_task_and_tag() {
+        let mut records = vec![
+            work(at(21, 10), 1500, PhaseOutcome::Completed, Some(1), &["cliente"]),
+            work(at(21, 11), 600, PhaseOutcome::Skipped, Some(1), &["cliente"]),
+            work(at(21, 12), 1500, PhaseOutcome::Completed, Some(2), &[]),
+        ];
+        let mut paused = records[0].clone();
+        paused.event = HistoryEvent::Paused;
+        records.push(paused);
+        let tasks = vec![Task { id: 1, title: "Leer".into(), estimate: None, pomodoros: 1, done: false, tags: Vec::new() }];
+        let now = Local.with_ymd_and_hms(2026, 10, 21, 18, 0, 0).unwrap();
+
+        let stats = compute(&records, &HistoryFilter::default(), HistoryRange::default(), &tasks, now, 8);
+        assert_eq!((stats.today, stats.pomodoros, stats.focus_seconds), (2, 2, 3600));
+        assert!((stats.completion_rate - 2.0 / 3.0).abs() < 1e-9);
+        assert!((stats.interruptions_per_phase - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(stats.tasks[0], TaskTotal { id: 1, title: Some("Leer".into()), pomodoros: 1, focus_seconds: 2100 });
+        assert_eq!(stats.tasks[1].title, None);
+        assert_eq!(stats.tags, vec![TagTotal { tag: "cliente".into(), pomodoros: 1, focus_seconds: 2100 }]);
+        assert_eq!(stats.best_hours[0].focus_seconds, 1500);
+        assert_eq!(stats.days.len(), 1);
+    }
+
+    #[test]
+    fn range_limits_totals_but_not_today_or_week() {
+        let records = [pomodoros(19, 2), pomodoros(21, 1)].concat();
+        let now = Local.with_ymd_and_hms(2026, 10, 21, 18, 0, 0).unwrap();
+        let range = HistoryRange::parse(Some("2026-10-19"), Some("2026-10-19")).unwrap();
+        let stats = compute(&records, &HistoryFilter::default(), range, &[], now, 8);
+        assert_eq!((stats.pomodoros, stats.today, stats.this_week), (2, 1, 3));
+    }
+
+    #[test]
+    fn filter_limits_totals_but_not_today_or_streaks() {
+        let records = [
+            pomodoros(20, 2),
+            vec![
+                work(at(21, 10), 1500, PhaseOutcome::Completed, None, &["cliente"]),
+                work(at(21, 11), 1500, PhaseOutcome::Completed, None, &[]),
+            ],
+        ]
+        .concat();
+        let now = Local.with_ymd_and_hms(2026, 10, 21, 18, 0, 0).unwrap();
+        let filter = HistoryFilter { tag: Some("cliente".into()), ..Default::default() };
+        let stats = compute(&records, &filter, HistoryRange::default(), &[], now, 2);
+        assert_eq!((stats.pomodoros, stats.today, stats.this_week), (1, 2, 4));
+        assert_eq!((stats.current_streak, stats.longest_streak), (2, 2));
+        assert_eq!(stats.days.len(), 1);
+    }
+
+    #[test]
+    fn streaks_count_consecutive_days_meeting_the_goal() {
+        let records = [pomodoros(14, 2), pomodoros(15, 2), pomodoros(16, 2), pomodoros(19, 2), pomodoros(20, 3), pomodoros(21, 1)].concat();
+        let now = Local.with_ymd_and_hms(2026, 10, 21, 18, 0, 0).unwrap();
+        let stats = compute(&records, &HistoryFilter::default(), HistoryRange::default(), &[], now, 2);
+        assert_eq!(stats.longest_streak, 3);
+        // Hoy aún no se ha llegado: la racha cuenta hasta ayer
+        assert_eq!(stats.current_streak, 2);
+        let stats = compute(&records, &HistoryFilter::default(), HistoryRange::default(), &[], now, 1);
+        assert_eq!(stats.current_streak, 3);
+    }
+}
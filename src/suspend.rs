@@ -0,0 +1,78 @@
+use std::process::Stdio;
+use std::time::Instant;
+
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+use tokio::sync::mpsc;
+use tokio::time::{sleep, Duration};
+
+/// Cada cuánto se comparan el reloj de pared y el monotónico
+const WALL_CLOCK_POLL_SECS: u64 = 2;
+/// Diferencia entre ambos relojes a partir de la que se asume una suspensión
+const SUSPEND_JUMP_SECS: i64 = 10;
+
+/// Cambios de energía o de sesión que afectan al temporizador.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerEvent {
+    /// El equipo va a suspenderse (solo con logind)
+    Sleeping,
+    /// El equipo ha vuelto tras `slept_secs` suspendido
+    Woke { slept_secs: u64 },
+    Locked,
+    Unlocked,
+}
+
+/// Detecta suspensiones a posteriori: el reloj monotónico no avanza mientras
+/// el equipo duerme y el de pared sí. Un cambio manual de hora también se
+/// ve como una suspensión; por eso es solo la alternativa a logind.
+pub async fn watch_wall_clock(events: mpsc::Sender<PowerEvent>) {
+    let mut last_wall = chrono::Utc::now().timestamp();
+    let mut last_mono = Instant::now();
+    loop {
+        sleep(Duration::from_secs(WALL_CLOCK_POLL_SECS)).await;
+
+        let wall = chrono::Utc::now().timestamp();
+        let mono = Instant::now();
+        let gap = (wall - last_wall) - mono.duration_since(last_mono).as_secs() as i64;
+        last_wall = wall;
+        last_mono = mono;
+
+        if gap >= SUSPEND_JUMP_SECS && events.send(PowerEvent::Woke { slept_secs: gap as u64 }).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Escucha las señales `PrepareForSleep`, `Lock` y `Unlock` de logind con
+/// `gdbus monitor`. Termina si `gdbus` no está o se cierra.
+pub async fn watch_logind(events: mpsc::Sender<PowerEvent>) -> std::io::Result<()> {
+    let mut child = Command::new("gdbus")
+        .args(["monitor", "--system", "--dest", "org.freedesktop.login1"])
+        .stdout(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+    let stdout = child.stdout.take().ok_or_else(|| std::io::Error::other("gdbus without stdout"))?;
+    let mut lines = BufReader::new(stdout).lines();
+
+    let mut sleeping_since = None;
+    while let Some(line) = lines.next_line().await? {
+        let event = if line.contains("PrepareForSleep (true,)") {
+            sleeping_since = Some(chrono::Utc::now().timestamp());
+            PowerEvent::Sleeping
+        } else if line.contains("PrepareForSleep (false,)") {
+            let since = sleeping_since.take().unwrap_or_else(|| chrono::Utc::now().timestamp());
+            let slept_secs = (chrono::Utc::now().timestamp() - since).max(0) as u64;
+            PowerEvent::Woke { slept_secs }
+        } else if line.contains("org.freedesktop.login1.Session.Lock ") {
+            PowerEvent::Locked
+        } else if line.contains("org.freedesktop.login1.Session.Unlock ") {
+            PowerEvent::Unlocked
+        } else {
+            continue;
+        };
+        if events.send(event).await.is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,155 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tokio::sync::{Mutex as AsyncMutex, MutexGuard};
+
+use crate::config::{data_file, ValidationError, MAX_CYCLES};
+
+/// Algo en lo que se trabaja. Cada fase de trabajo completada con la tarea
+/// activa (`settask`) suma un pomodoro.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Task {
+    pub id: u32,
+    pub title: String,
+    /// Pomodoros que se calcula que llevará
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimate: Option<u32>,
+    /// Pomodoros completados con la tarea activa
+    #[serde(default)]
+    pub pomodoros: u32,
+    #[serde(default)]
+    pub done: bool,
+    /// Etiquetas que se copian en el historial de cada fase de la tarea
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+pub const MAX_TASK_TITLE_LEN: usize = 200;
+pub const MAX_TASK_TAGS: usize = 10;
+pub const MAX_TAG_LEN: usize = 32;
+
+pub fn tasks_path() -> String {
+    data_file("tasks.json")
+}
+
+/// Cerrojo de `tasks.json`. El servicio crea uno y lo comparte con el
+/// temporizador; quien lee y reescribe la lista lo mantiene mientras tanto.
+#[derive(Clone, Default)]
+pub struct TasksLock(Arc<AsyncMutex<()>>);
+
+impl TasksLock {
+    pub async fn lock(&self) -> MutexGuard<'_, ()> {
+        self.0.lock().await
+    }
+}
+
+pub async fn read_tasks() -> Vec<Task> {
+    if !Path::new(&tasks_path()).exists() {
+        return Vec::new();
+    }
+
+    let content = fs::read_to_string(&tasks_path()).await.unwrap_or_default();
+    match serde_json::from_str(&content) {
+        Ok(tasks) => tasks,
+        Err(e) => {
+            eprintln!("❌ Error al leer las tareas: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+pub async fn save_tasks(tasks: &[Task]) -> std::io::Result<()> {
+    let json = serde_json::to_string_pretty(tasks).map_err(std::io::Error::other)?;
+    let path = tasks_path();
+    let tmp_path = format!("{}.tmp", path);
+    fs::write(&tmp_path, json).await?;
+    fs::rename(&tmp_path, &path).await
+}
+
+/// Los ids no se reutilizan mientras quede alguna tarea con uno mayor.
+pub fn next_task_id(tasks: &[Task]) -> u32 {
+    tasks.iter().map(|task| task.id).max().unwrap_or(0) + 1
+}
+
+pub fn validate_task(task: &Task) -> Result<(), ValidationError> {
+    if task.title.trim().is_empty() || task.title.chars().count() > MAX_TASK_TITLE_LEN {
+        return Err(ValidationError {
+            field: "title",
+            message: format!("must be between 1 and {} characters", MAX_TASK_TITLE_LEN),
+        });
+    }
+    if task.estimate.is_some_and(|estimate| estimate == 0 || estimate > MAX_CYCLES) {
+        return Err(ValidationError {
+            field: "estimate",
+            message: format!("must be between 1 and {}", MAX_CYCLES),
+        });
+    }
+    if task.tags.len() > MAX_TASK_TAGS {
+        return Err(ValidationError {
+            field: "tags",
+            message: format!("at most {} tags", MAX_TASK_TAGS),
+        });
+    }
+    if task.tags.iter().any(|tag| !is_tag(tag)) {
+        return Err(ValidationError {
+            field: "tags",
+            message: format!("tags must be 1 to {} characters without spaces or commas", MAX_TAG_LEN),
+        });
+    }
+    Ok(())
+}
+
+/// Sin espacios ni comas, para que se puedan filtrar y exportar tal cual.
+fn is_tag(tag: &str) -> bool {
+    !tag.is_empty() && tag.chars().count() <= MAX_TAG_LEN && !tag.contains(|c: char| c.is_whitespace() || c == ',')
+}
+
+/// Etiquetas de la tarea `id`, o ninguna si no existe. Lee sin el cerrojo:
+/// `save_tasks` renombra el archivo entero, así que nunca se ve a medias.
+pub async fn task_tags(id: u32) -> Vec<String> {
+    read_tasks().await.into_iter().find(|task| task.id == id).map(|task| task.tags).unwrap_or_default()
+}
+
+/// Suma un pomodoro a la tarea `id`. Se llama al completar una fase de
+/// trabajo con esa tarea activa.
+pub async fn record_pomodoro(lock: &TasksLock, id: u32) {
+    let _tasks = lock.lock().await;
+    let mut tasks = read_tasks().await;
+    let Some(task) = tasks.iter_mut().find(|task| task.id == id) else {
+        return;
+    };
+    task.pomodoros += 1;
+    println!("📝 Pomodoro {} para «{}»", task.pomodoros, task.title);
+    if let Err(e) = save_tasks(&tasks).await {
+        eprintln!("❌ Error al guardar las tareas: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: u32, title: &str) -> Task {
+        Task { id, title: title.to_string(), estimate: None, pomodoros: 0, done: false, tags: Vec::new() }
+    }
+
+    #[test]
+    fn ids_continue_after_the_highest() {
+        assert_eq!(next_task_id(&[]), 1);
+        assert_eq!(next_task_id(&[task(4, "a"), task(2, "b")]), 5);
+    }
+
+    #[test]
+    fn title_and_estimate_are_checked() {
+        assert!(validate_task(&task(1, "Escribir informe")).is_ok());
+        assert_eq!(validate_task(&task(1, "  ")).unwrap_err().field, "title");
+        let mut zero = task(1, "a");
+        zero.estimate = Some(0);
+        assert_eq!(validate_task(&zero).unwrap_err().field, "estimate");
+        let mut tagged = task(1, "a");
+        tagged.tags = vec!["cliente".into(), "dos palabras".into()];
+        assert_eq!(validate_task(&tagged).unwrap_err().field, "tags");
+    }
+}
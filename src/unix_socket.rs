@@ -0,0 +1,91 @@
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::{fs, io, path::Path};
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::task;
+
+use crate::config::runtime_file;
+use crate::protocol::{ApiError, TimerRequest};
+use crate::service::PomodoroService;
+
+pub fn socket_path() -> String {
+    runtime_file("pomodoro.sock")
+}
+
+/// Crea el socket de `socket_path`. Falla si otro servidor ya escucha en él;
+/// un socket abandonado por un servidor caído se sustituye.
+pub fn bind_unix_socket() -> io::Result<UnixListener> {
+    let path = socket_path();
+    if Path::new(&path).exists() {
+        // Si alguien contesta, es otro servidor en marcha y su socket no se toca
+        if std::os::unix::net::UnixStream::connect(&path).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("another Pomodoro server is listening on {}", path),
+            ));
+        }
+        fs::remove_file(&path)?;
+    }
+    bind_private(&path)
+}
+
+/// Un comando JSON por línea y una respuesta JSON por línea. No pide token:
+/// el socket se crea con permisos 0600, así que solo el propio usuario entra.
+pub async fn start_unix_server(service: PomodoroService, listener: UnixListener) -> std::io::Result<()> {
+    let path = socket_path();
+    println!("🧦 Unix socket Pomodoro server on {}", path);
+
+    let mut shutdown = service.shutdown_signal();
+    loop {
+        let (stream, _) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = shutdown.wait_for(|stop| *stop) => break,
+        };
+        let service = service.clone();
+        task::spawn(async move {
+            if let Err(e) = handle_unix_connection(stream, service).await {
+                eprintln!("❌ Error en conexión Unix: {}", e);
+            }
+        });
+    }
+
+    fs::remove_file(&path)
+}
+
+/// Crea el socket dentro de un directorio 0700 propio, le da permisos 0600
+/// y solo entonces lo mueve a `path`: nunca hay un momento en que otro
+/// usuario pueda conectarse.
+fn bind_private(path: &str) -> io::Result<UnixListener> {
+    let staging = format!("{}.{}.d", path, std::process::id());
+    fs::DirBuilder::new().mode(0o700).create(&staging)?;
+    let staged = Path::new(&staging).join("pomodoro.sock");
+    let bound = UnixListener::bind(&staged)
+        .and_then(|listener| fs::set_permissions(&staged, fs::Permissions::from_mode(0o600)).map(|()| listener))
+        .and_then(|listener| fs::rename(&staged, path).map(|()| listener));
+    let _ = fs::remove_file(&staged);
+    fs::remove_dir(&staging)?;
+    bound
+}
+
+async fn handle_unix_connection(stream: UnixStream, service: PomodoroService) -> std::io::Result<()> {
+    let (read, mut write) = stream.into_split();
+    let mut lines = BufReader::new(read).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        let input = line.trim();
+        if input.is_empty() {
+            continue;
+        }
+
+        let response = match serde_json::from_str::<TimerRequest>(input) {
+            Ok(request) => service.execute_on(request.timer.as_deref(), request.command).await,
+            Err(e) => ApiError::invalid(e.to_string()).into(),
+        };
+        let mut response_text = serde_json::to_string(&response).map_err(std::io::Error::other)?;
+        response_text.push('\n');
+        write.write_all(response_text.as_bytes()).await?;
+    }
+
+    Ok(())
+}
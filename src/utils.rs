@@ -1,36 +1,56 @@
-use std::io::{BufRead, BufReader, Write};
-use std::net::TcpStream;
-use std::process::Command;
+use std::io::Write;
+use std::process::{Command, Stdio};
 use std::fs;
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
 
-use crate::config::PomodoroStateConfig;
-
-
-
-fn blocked_sites_path() -> String {
-    if cfg!(debug_assertions) {
-        "blocked_sites.json".to_string()
-    } else {
-        "/etc/global_pomodoro/blocked_sites.json".to_string()
-    }
-}
+use crate::blocked_sites::{blocked_sites_path, is_hostname};
 
 pub struct SiteBlocker {
     pub hosts_file: String,
+    /// Mientras está retenido, el cambio de fase no levanta el bloqueo
+    held: AtomicBool,
+    /// Bloqueo simulado: no toca el hosts ni ejecuta `sudo`
+    simulated: Option<AtomicBool>,
 }
 
 impl SiteBlocker {
     pub fn new(hosts_file: Option<&str>) -> Self {
         Self {
             hosts_file: hosts_file.unwrap_or("/etc/hosts").to_string(),
+            held: AtomicBool::new(false),
+            simulated: None,
+        }
+    }
+
+    /// Bloqueador que solo recuerda si está bloqueando, para pruebas.
+    pub fn simulated() -> Self {
+        Self {
+            simulated: Some(AtomicBool::new(false)),
+            ..Self::new(None)
         }
     }
 
+    /// Retiene (o suelta) el bloqueo, p. ej. durante una ventana de la agenda.
+    pub fn set_held(&self, held: bool) {
+        self.held.store(held, Ordering::Relaxed);
+    }
+
+    pub fn is_held(&self) -> bool {
+        self.held.load(Ordering::Relaxed)
+    }
+
     fn load_blocked_sites(&self, path: &str) -> std::io::Result<Vec<String>> {
         let data = fs::read_to_string(path)?;
-        let sites: Vec<String> = serde_json::from_str(&data)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+        let mut sites: Vec<String> = serde_json::from_str(&data)
+            .map_err(std::io::Error::other)?;
+        sites.retain(|site| {
+            let valid = is_hostname(site);
+            if !valid {
+                eprintln!("Ignoring invalid blocked site: {:?}", site);
+            }
+            valid
+        });
         Ok(sites)
     }
 
@@ -45,73 +65,168 @@ impl SiteBlocker {
         }
     }
 
+    /// Escribe `content` en el hosts con `sudo tee`, sin pasar por un shell.
+    fn write_hosts(&self, content: &str, append: bool) -> Result<(), String> {
+        let mut command = Command::new("sudo");
+        command.arg("tee");
+        if append {
+            command.arg("-a");
+        }
+        let mut child = command
+            .arg(&self.hosts_file)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to run sudo command: {}", e))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(content.as_bytes()).map_err(|e| e.to_string())?;
+        }
+        let output = child.wait_with_output().map_err(|e| e.to_string())?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(String::from_utf8_lossy(&output.stderr).into_owned())
+        }
+    }
+
     pub fn block(&self) {
+        if let Some(blocking) = &self.simulated {
+            blocking.store(true, Ordering::Relaxed);
+            return;
+        }
         let content = fs::read_to_string(&self.hosts_file).unwrap_or_default();
-        let blocked_sites = self.load_blocked_sites(&blocked_sites_path())
-            .expect("Failed to load blocked sites");
-
-        for site in &blocked_sites {
-            let redirect_entry = format!("0.0.0.0 {}", site);
-
-            if !content.contains(&redirect_entry) {
-                let output = Command::new("sudo")
-                    .arg("sh")
-                    .arg("-c")
-                    .arg(format!("echo '{}' >> {}", redirect_entry, self.hosts_file))
-                    .output()
-                    .expect("Failed to run sudo command");
+        let blocked_sites = match self.load_blocked_sites(&blocked_sites_path()) {
+            Ok(sites) => sites,
+            Err(e) => {
+                eprintln!("Failed to load blocked sites: {}", e);
+                return;
+            }
+        };
 
-                if output.status.success() {
+        let missing: Vec<&String> = blocked_sites
+            .iter()
+            .filter(|site| !content.lines().any(|line| line.trim() == format!("0.0.0.0 {}", site)))
+            .collect();
+        if missing.is_empty() {
+            return;
+        }
+        let mut entries = String::new();
+        if !content.is_empty() && !content.ends_with('\n') {
+            entries.push('\n');
+        }
+        for site in &missing {
+            entries.push_str(&format!("0.0.0.0 {}\n", site));
+        }
+        match self.write_hosts(&entries, true) {
+            Ok(()) => {
+                for site in missing {
                     println!("Blocked domain: {}", site);
-                } else {
-                    eprintln!(
-                        "Failed to block domain: {} (Error: {})",
-                        site,
-                        String::from_utf8_lossy(&output.stderr)
-                    );
                 }
             }
+            Err(e) => eprintln!("Failed to block domains (Error: {})", e),
         }
         self.reset_service();
     }
 
+    /// Indica si alguno de los sitios de la lista sigue redirigido en el hosts.
+    pub fn is_blocking(&self) -> bool {
+        if let Some(blocking) = &self.simulated {
+            return blocking.load(Ordering::Relaxed);
+        }
+        let content = fs::read_to_string(&self.hosts_file).unwrap_or_default();
+        let blocked_sites = self.load_blocked_sites(&blocked_sites_path()).unwrap_or_default();
+        blocked_sites
+            .iter()
+            .any(|site| content.lines().any(|line| line.trim() == format!("0.0.0.0 {}", site)))
+    }
+
     pub fn unblock(&self) {
-        let blocked_sites = self.load_blocked_sites(&blocked_sites_path())
-            .expect("Failed to load blocked sites");
-
-        for site in &blocked_sites {
-            let redirect_entry = format!("0.0.0.0 {}", site);
-
-            let output = Command::new("sudo")
-                .arg("sh")
-                .arg("-c")
-                .arg(format!("sed -i '/{}/d' {}", redirect_entry, self.hosts_file))
-                .output()
-                .expect("Failed to run sudo command");
-
-            if output.status.success() {
-                println!("Unblocked domain: {}", site);
-            } else {
-                eprintln!(
-                    "Failed to unblock domain: {} (Error: {})",
-                    site,
-                    String::from_utf8_lossy(&output.stderr)
-                );
+        if let Some(blocking) = &self.simulated {
+            blocking.store(false, Ordering::Relaxed);
+            return;
+        }
+        let blocked_sites = match self.load_blocked_sites(&blocked_sites_path()) {
+            Ok(sites) => sites,
+            Err(e) => {
+                eprintln!("Failed to load blocked sites: {}", e);
+                return;
+            }
+        };
+        let content = match fs::read_to_string(&self.hosts_file) {
+            Ok(content) => content,
+            Err(e) => {
+                eprintln!("Failed to read {}: {}", self.hosts_file, e);
+                return;
+            }
+        };
+
+        // Solo se quitan las líneas idénticas a las que añade `block`
+        let entries: Vec<String> = blocked_sites.iter().map(|site| format!("0.0.0.0 {}", site)).collect();
+        let kept: Vec<&str> = content.lines().filter(|line| !entries.iter().any(|entry| line.trim() == entry)).collect();
+        if kept.len() == content.lines().count() {
+            return;
+        }
+        let mut updated = kept.join("\n");
+        updated.push('\n');
+        match self.write_hosts(&updated, false) {
+            Ok(()) => {
+                for site in &blocked_sites {
+                    println!("Unblocked domain: {}", site);
+                }
             }
+            Err(e) => eprintln!("Failed to unblock domains (Error: {})", e),
         }
         self.reset_service();
     }
 }
 
 
+/// Avisos y sonidos del temporizador. `DesktopNotifier` usa `notify-send` y
+/// `mpg123`; `RecordingNotifier` solo los anota, para pruebas.
+pub trait Notifier: Send + Sync {
+    fn notify(&self, title: &str, message: &str);
+    fn play_sound(&self, file: &str);
+}
+
+pub struct DesktopNotifier;
+
+impl Notifier for DesktopNotifier {
+    fn notify(&self, title: &str, message: &str) {
+        show_notification(title, message);
+    }
+
+    fn play_sound(&self, file: &str) {
+        play_sound(file);
+    }
+}
+
+#[derive(Default)]
+pub struct RecordingNotifier {
+    pub notifications: Mutex<Vec<String>>,
+    pub sounds: Mutex<Vec<String>>,
+}
+
+impl Notifier for RecordingNotifier {
+    fn notify(&self, title: &str, _message: &str) {
+        self.notifications.lock().unwrap().push(title.to_string());
+    }
+
+    fn play_sound(&self, file: &str) {
+        self.sounds.lock().unwrap().push(file.to_string());
+    }
+}
+
 pub fn show_notification(title: &str, message: &str) {
-Command::new("notify-send")
-    .arg("--urgency=normal")
-    .arg("--icon=appointment-soon")
-    .arg(title)
-    .arg(message)
-    .status()
-    .expect("Failed to send notification");
+    if let Err(e) = Command::new("notify-send")
+        .arg("--urgency=normal")
+        .arg("--icon=appointment-soon")
+        .arg(title)
+        .arg(message)
+        .status()
+    {
+        eprintln!("❌ Failed to send notification: {}", e);
+    }
 }
 
 pub fn play_sound(file: &str) {